@@ -0,0 +1,73 @@
+use std::{
+	env,
+	process::Command,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+fn git_commit() -> String {
+	if let Ok(commit) = env::var("TOUCHID_GIT_COMMIT") {
+		return commit;
+	}
+
+	Command::new("git")
+		.args(["rev-parse", "--short=12", "HEAD"])
+		.output()
+		.ok()
+		.filter(|output| output.status.success())
+		.map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+		.unwrap_or_else(|| "unknown".to_string())
+}
+
+// honours SOURCE_DATE_EPOCH for reproducible builds
+fn build_timestamp() -> String {
+	let secs = env::var("SOURCE_DATE_EPOCH")
+		.ok()
+		.and_then(|secs| secs.parse::<u64>().ok())
+		.unwrap_or_else(|| {
+			SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.map(|d| d.as_secs())
+				.unwrap_or_default()
+		});
+
+	rfc3339(secs)
+}
+
+// days-to-civil conversion from http://howardhinnant.github.io/date_algorithms.html
+fn rfc3339(secs: u64) -> String {
+	let days = (secs / 86_400) as i64;
+	let rem = secs % 86_400;
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let doe = z.rem_euclid(146_097);
+	let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + i64::from(month <= 2);
+
+	format!(
+		"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+		year,
+		month,
+		day,
+		rem / 3_600,
+		rem % 3_600 / 60,
+		rem % 60
+	)
+}
+
+fn main() {
+	println!("cargo:rustc-env=TOUCHID_GIT_COMMIT={}", git_commit());
+	println!(
+		"cargo:rustc-env=TOUCHID_BUILD_TIMESTAMP={}",
+		build_timestamp()
+	);
+	println!("cargo:rerun-if-env-changed=TOUCHID_GIT_COMMIT");
+	println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+	println!("cargo:rerun-if-changed=.git/HEAD");
+	println!("cargo:rerun-if-changed=.git/refs");
+	// the sql migrations are embedded into the binary
+	println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,2 @@
+ALTER TABLE locks ADD COLUMN expires_at BIGINT;
+CREATE INDEX IF NOT EXISTS locks_expires_at ON locks (expires_at);
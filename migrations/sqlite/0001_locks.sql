@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS locks (
+	id TEXT PRIMARY KEY NOT NULL,
+	token TEXT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE locks ADD COLUMN uses_left BIGINT;
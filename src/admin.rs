@@ -0,0 +1,73 @@
+use axum::{
+	extract,
+	http::Request,
+	middleware::{self, Next},
+	response::{IntoResponse, Response},
+	routing::{delete, get, post},
+	Router,
+};
+
+use crate::{
+	announcements, auth, honeypot, json::Reply, lock, logging, runtime, slo, status, Error, State,
+};
+
+// admin routes are only mounted when an admin token is configured
+pub fn router(state: State) -> Router<State> {
+	let mut router = Router::new()
+		.route("/slo", get(slo))
+		.route("/runtime", get(runtime))
+		.route("/honeypot", get(honeypot::hits))
+		.route("/status/incidents", post(status::open_incident))
+		.route("/status/incidents/:id", delete(status::resolve_incident))
+		.route(
+			"/announcements",
+			get(announcements::list).post(announcements::add),
+		)
+		.route("/announcements/:id", delete(announcements::remove));
+
+	if state.log_filter.is_some() {
+		router = router.route(
+			"/log-level",
+			get(logging::get_level).put(logging::set_level),
+		);
+	}
+
+	router.route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+pub async fn require_admin<B>(
+	extract::State(state): extract::State<State>,
+	req: Request<B>,
+	next: Next<B>,
+) -> Response {
+	match (auth::bearer(req.headers()), state.admin_token.as_deref()) {
+		(Some(provided), Some(expected))
+			if lock::constant_time_eq(provided.as_bytes(), expected.as_bytes()) =>
+		{
+			next.run(req).await
+		}
+		_ => Error::Unauthorized.into_response(),
+	}
+}
+
+#[utoipa::path(
+	get,
+	path = "/admin/slo",
+	tag = "admin",
+	responses((status = 200, description = "latency and error budgets per route", body = Vec<slo::Status>)),
+	security(("admin_token" = [])),
+)]
+pub async fn slo(extract::State(state): extract::State<State>) -> Reply<Vec<slo::Status>> {
+	state.json.reply(state.slo.report())
+}
+
+#[utoipa::path(
+	get,
+	path = "/admin/runtime",
+	tag = "admin",
+	responses((status = 200, description = "tokio runtime metrics", body = runtime::Report)),
+	security(("admin_token" = [])),
+)]
+pub async fn runtime(extract::State(state): extract::State<State>) -> Reply<runtime::Report> {
+	state.json.reply(runtime::report())
+}
@@ -0,0 +1,314 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	sync::Mutex,
+};
+
+use axum::{
+	extract,
+	http::header,
+	response::{IntoResponse, Response},
+};
+use serde::{self, Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+	lock,
+	params::{Path, Query},
+	Error, State,
+};
+
+const HOUR: u64 = 3_600;
+const DAY: u64 = 24 * HOUR;
+const RETENTION_DAYS: u64 = 90;
+const DEFAULT_DAYS: u64 = 7;
+// ids are made up by clients, so the least recently unlocked are forgotten past this many
+const MAX_TRACKED: usize = 10_000;
+
+// unlocks per lock and hour, as seen by this process; kept for RETENTION_DAYS
+#[derive(Debug)]
+pub struct Occupancy {
+	inner: Mutex<Tracked>,
+	max_tracked: usize,
+}
+
+#[derive(Default, Debug)]
+struct Tracked {
+	hours: HashMap<String, Counts>,
+	// ids by the sequence number of their last unlock, oldest first
+	recent: BTreeMap<u64, String>,
+	next: u64,
+}
+
+#[derive(Default, Debug)]
+struct Counts {
+	hours: BTreeMap<u64, u64>,
+	last: u64,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct Hour {
+	pub hour: String,
+	pub unlocks: u64,
+}
+
+impl Default for Occupancy {
+	fn default() -> Self {
+		Self::new(MAX_TRACKED)
+	}
+}
+
+impl Occupancy {
+	fn new(max_tracked: usize) -> Self {
+		Self {
+			inner: Mutex::default(),
+			max_tracked,
+		}
+	}
+
+	pub fn record(&self, id: &str) {
+		let mut tracked = self.inner.lock().unwrap();
+		let Tracked {
+			hours,
+			recent,
+			next,
+		} = &mut *tracked;
+		let seq = *next;
+		*next += 1;
+
+		if let Some(counts) = hours.get_mut(id) {
+			recent.remove(&counts.last);
+		} else {
+			while hours.len() >= self.max_tracked {
+				let Some((_, oldest)) = recent.pop_first() else {
+					break;
+				};
+
+				hours.remove(&oldest);
+			}
+		}
+
+		let counts = hours.entry(id.to_string()).or_default();
+		counts.last = seq;
+		*counts.hours.entry(lock::now() / HOUR).or_default() += 1;
+		recent.insert(seq, id.to_string());
+	}
+
+	// every hour in [from, to), including the ones without unlocks
+	pub fn hourly(&self, id: &str, from: u64, to: u64) -> Vec<Hour> {
+		let tracked = self.inner.lock().unwrap();
+		let counts = tracked.hours.get(id).map(|counts| &counts.hours);
+
+		(from..to)
+			.map(|hour| Hour {
+				hour: format_hour(hour),
+				unlocks: counts
+					.and_then(|counts| counts.get(&hour).copied())
+					.unwrap_or_default(),
+			})
+			.collect()
+	}
+
+	pub fn prune(&self, now: u64) {
+		let oldest = now.saturating_sub(RETENTION_DAYS * DAY) / HOUR;
+
+		let mut tracked = self.inner.lock().unwrap();
+		let Tracked { hours, recent, .. } = &mut *tracked;
+
+		hours.retain(|_, counts| {
+			counts.hours = counts.hours.split_off(&oldest);
+
+			if counts.hours.is_empty() {
+				recent.remove(&counts.last);
+			}
+
+			!counts.hours.is_empty()
+		});
+	}
+}
+
+// days-to-civil and back, from http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let doe = z.rem_euclid(146_097);
+	let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+	(yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let yoe = year - era * 400;
+	let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+	era * 146_097 + doe - 719_468
+}
+
+fn format_hour(hour: u64) -> String {
+	let (year, month, day) = civil_from_days((hour / 24) as i64);
+
+	format!(
+		"{:04}-{:02}-{:02}T{:02}:00:00Z",
+		year,
+		month,
+		day,
+		hour % 24
+	)
+}
+
+// a YYYY-MM-DD date in UTC, as days since the epoch
+fn parse_date(date: &str) -> Result<u64, Error> {
+	let invalid = || Error::BadRequest(format!("invalid date: {}", date));
+	let mut parts = date.splitn(3, '-').map(|part| part.parse::<i64>());
+	let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) =
+		(parts.next(), parts.next(), parts.next())
+	else {
+		return Err(invalid());
+	};
+
+	// also keeps days_from_civil far away from overflowing
+	if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+		return Err(invalid());
+	}
+
+	let days = days_from_civil(year, month, day);
+
+	// rejects dates like 2026-02-30 that would silently roll over
+	if civil_from_days(days) != (year, month, day) {
+		return Err(invalid());
+	}
+
+	Ok(days as u64)
+}
+
+#[derive(Deserialize, Default, Debug, ToSchema)]
+#[serde(crate = "self::serde", rename_all = "lowercase")]
+pub enum Format {
+	#[default]
+	Json,
+	Csv,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[serde(crate = "self::serde")]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+	// inclusive dates; the last week when omitted
+	pub from: Option<String>,
+	pub to: Option<String>,
+	#[serde(default)]
+	pub format: Format,
+}
+
+#[utoipa::path(
+	get,
+	path = "/locks/{id}/analytics",
+	tag = "locks",
+	params(("id" = String, Path, description = "lock id"), Params),
+	responses(
+		(
+			status = 200,
+			description = "unlocks per hour",
+			content((Vec<Hour> = "application/json"), (String = "text/csv")),
+		),
+		(status = 400, description = "the date range is malformed or too long", body = crate::error::ErrorBody),
+	),
+	security((), ("api_token" = [])),
+)]
+pub async fn occupancy(
+	extract::State(state): extract::State<State>,
+	Path(id): Path<String>,
+	Query(params): Query<Params>,
+) -> Result<Response, Error> {
+	let to = match &params.to {
+		Some(to) => parse_date(to)?,
+		None => lock::now() / DAY,
+	};
+	let from = match &params.from {
+		Some(from) => parse_date(from)?,
+		None => to.saturating_sub(DEFAULT_DAYS - 1),
+	};
+
+	if from > to || to - from >= RETENTION_DAYS {
+		return Err(Error::BadRequest(format!(
+			"the range must be ordered and span at most {} days",
+			RETENTION_DAYS
+		)));
+	}
+
+	let hours = state.occupancy.hourly(&id, from * 24, (to + 1) * 24);
+
+	Ok(match params.format {
+		Format::Json => state.json.reply(hours).into_response(),
+		Format::Csv => {
+			let csv = hours
+				.iter()
+				.fold(String::from("hour,unlocks\n"), |csv, hour| {
+					csv + &format!("{},{}\n", hour.hour, hour.unlocks)
+				});
+
+			([(header::CONTENT_TYPE, "text/csv")], csv).into_response()
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn dates_parse_to_days_since_the_epoch() {
+		assert_eq!(parse_date("1970-01-01").unwrap(), 0);
+		assert_eq!(parse_date("2024-02-29").unwrap(), 19_782);
+		assert_eq!(parse_date("9999-12-31").unwrap(), 2_932_896);
+	}
+
+	#[test]
+	fn malformed_dates_are_rejected() {
+		for date in [
+			"",
+			"2024",
+			"2024-13-01",
+			"2023-02-29",
+			"2024-04-31",
+			"1969-12-31",
+			"10000-01-01",
+			"100000000000000000-01-01",
+			"2024-01-01-01",
+			"-2024-01-01",
+		] {
+			assert!(
+				matches!(parse_date(date), Err(Error::BadRequest(_))),
+				"{}",
+				date
+			);
+		}
+	}
+
+	#[test]
+	fn the_least_recently_unlocked_ids_are_forgotten() {
+		let occupancy = Occupancy::new(2);
+		let hour = lock::now() / HOUR;
+		let unlocks = |id| occupancy.hourly(id, hour, hour + 1)[0].unlocks;
+
+		occupancy.record("a");
+		occupancy.record("b");
+		occupancy.record("a");
+		occupancy.record("c");
+
+		assert_eq!(unlocks("a"), 2);
+		assert_eq!(unlocks("b"), 0);
+		assert_eq!(unlocks("c"), 1);
+
+		let tracked = occupancy.inner.lock().unwrap();
+		assert_eq!(tracked.hours.len(), 2);
+		assert_eq!(tracked.recent.len(), 2);
+	}
+}
@@ -0,0 +1,198 @@
+use std::sync::{
+	atomic::{AtomicU64, Ordering},
+	RwLock,
+};
+
+use axum::{
+	extract,
+	http::{HeaderValue, Request, StatusCode},
+	middleware::Next,
+	response::Response,
+};
+use serde::{self, Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+	json::{Body, Reply},
+	lock,
+	params::Path,
+	Error, State,
+};
+
+pub const HEADER: &str = "x-announcement";
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct Announcement {
+	pub id: u64,
+	pub message: String,
+	pub starts_at: Option<u64>,
+	pub ends_at: Option<u64>,
+}
+
+impl Announcement {
+	fn is_active(&self, now: u64) -> bool {
+		self.starts_at.is_none_or(|at| at <= now) && self.ends_at.is_none_or(|at| now < at)
+	}
+}
+
+#[derive(Default, Debug)]
+pub struct Announcements {
+	announcements: RwLock<Vec<Announcement>>,
+	next_id: AtomicU64,
+}
+
+impl Announcements {
+	pub fn add(
+		&self,
+		message: String,
+		starts_at: Option<u64>,
+		ends_at: Option<u64>,
+	) -> Announcement {
+		let announcement = Announcement {
+			id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+			message,
+			starts_at,
+			ends_at,
+		};
+
+		self.announcements
+			.write()
+			.unwrap()
+			.push(announcement.clone());
+
+		announcement
+	}
+
+	pub fn remove(&self, id: u64) -> bool {
+		let mut announcements = self.announcements.write().unwrap();
+		let before = announcements.len();
+		announcements.retain(|announcement| announcement.id != id);
+
+		announcements.len() != before
+	}
+
+	pub fn list(&self) -> Vec<Announcement> {
+		self.announcements.read().unwrap().clone()
+	}
+
+	// oldest first
+	pub fn active(&self) -> Vec<Announcement> {
+		let now = lock::now();
+
+		self.announcements
+			.read()
+			.unwrap()
+			.iter()
+			.filter(|announcement| announcement.is_active(now))
+			.cloned()
+			.collect()
+	}
+}
+
+// the newest active announcement travels with every response
+pub async fn header<B>(
+	extract::State(state): extract::State<State>,
+	req: Request<B>,
+	next: Next<B>,
+) -> Response {
+	let mut res = next.run(req).await;
+	let latest = state.announcements.active().pop();
+
+	// messages are checked to be valid header values when they are added
+	if let Some(value) = latest.and_then(|latest| HeaderValue::from_str(&latest.message).ok()) {
+		res.headers_mut().insert(HEADER, value);
+	}
+
+	res
+}
+
+#[utoipa::path(
+	get,
+	path = "/announcements",
+	operation_id = "active_announcements",
+	tag = "service",
+	responses((status = 200, description = "announcements shown right now", body = Vec<Announcement>)),
+)]
+pub async fn active(extract::State(state): extract::State<State>) -> Reply<Vec<Announcement>> {
+	state.json.reply(state.announcements.active())
+}
+
+#[utoipa::path(
+	get,
+	path = "/admin/announcements",
+	operation_id = "list_announcements",
+	tag = "admin",
+	responses((status = 200, description = "every announcement, including scheduled and ended ones", body = Vec<Announcement>)),
+	security(("admin_token" = [])),
+)]
+pub async fn list(extract::State(state): extract::State<State>) -> Reply<Vec<Announcement>> {
+	state.json.reply(state.announcements.list())
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct AnnouncementRequest {
+	pub message: String,
+	// unix seconds; shown right away and until removed when omitted
+	#[serde(default)]
+	pub starts_at: Option<u64>,
+	#[serde(default)]
+	pub ends_at: Option<u64>,
+}
+
+#[utoipa::path(
+	post,
+	path = "/admin/announcements",
+	operation_id = "add_announcement",
+	tag = "admin",
+	request_body = AnnouncementRequest,
+	responses(
+		(status = 201, description = "the announcement was added", body = Announcement),
+		(status = 400, description = "the message does not fit a header", body = crate::error::ErrorBody),
+	),
+	security(("admin_token" = [])),
+)]
+pub async fn add(
+	extract::State(state): extract::State<State>,
+	Body(req): Body<AnnouncementRequest>,
+) -> Result<(StatusCode, Reply<Announcement>), Error> {
+	if req.message.is_empty() || HeaderValue::from_str(&req.message).is_err() {
+		return Err(Error::BadRequest(
+			"message must be non-empty and free of control characters to fit the X-Announcement header"
+				.to_string(),
+		));
+	}
+
+	let announcement = state
+		.announcements
+		.add(req.message, req.starts_at, req.ends_at);
+	tracing::info!(id = announcement.id, message = %announcement.message, "announcement added");
+
+	Ok((StatusCode::CREATED, state.json.reply(announcement)))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/admin/announcements/{id}",
+	operation_id = "remove_announcement",
+	tag = "admin",
+	params(("id" = u64, Path, description = "announcement id")),
+	responses(
+		(status = 204, description = "the announcement was removed"),
+		(status = 410, description = "no such announcement", body = crate::error::ErrorBody),
+	),
+	security(("admin_token" = [])),
+)]
+pub async fn remove(
+	extract::State(state): extract::State<State>,
+	Path(id): Path<u64>,
+) -> Result<StatusCode, Error> {
+	if state.announcements.remove(id) {
+		tracing::info!(id, "announcement removed");
+
+		Ok(StatusCode::NO_CONTENT)
+	} else {
+		Err(Error::NotFound)
+	}
+}
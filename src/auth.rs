@@ -0,0 +1,138 @@
+use axum::{
+	extract,
+	http::{header, HeaderMap, Request},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+
+use crate::{lock, Error, State};
+
+pub fn bearer(headers: &HeaderMap) -> Option<&str> {
+	headers
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "))
+}
+
+// several tokens may be valid at once so that they can be rotated without downtime
+pub async fn require_api_token<B>(
+	extract::State(state): extract::State<State>,
+	req: Request<B>,
+	next: Next<B>,
+) -> Response {
+	let authorized = bearer(req.headers()).is_some_and(|provided| {
+		state
+			.api_tokens
+			.iter()
+			.any(|token| lock::constant_time_eq(provided.as_bytes(), token.as_bytes()))
+	});
+
+	if authorized {
+		next.run(req).await
+	} else {
+		Error::Unauthorized.into_response()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use axum::{body::Body, http::StatusCode, Router};
+	use tower::ServiceExt;
+
+	use super::*;
+	use crate::router;
+
+	async fn status(app: &Router, method: &str, path: &str, token: Option<&str>) -> StatusCode {
+		let mut req = Request::builder()
+			.method(method)
+			.uri(path)
+			.header(header::CONTENT_TYPE, "application/json");
+
+		if let Some(token) = token {
+			req = req.header(header::AUTHORIZATION, format!("Bearer {}", token));
+		}
+
+		let body = if method == "POST" {
+			Body::from(r#"{"token":"abcdefgh"}"#)
+		} else {
+			Body::empty()
+		};
+
+		app.clone()
+			.oneshot(req.body(body).unwrap())
+			.await
+			.unwrap()
+			.status()
+	}
+
+	fn tokens() -> Vec<String> {
+		vec!["old".to_string(), "new".to_string()]
+	}
+
+	#[tokio::test]
+	async fn writes_need_any_configured_token() {
+		let app = router(State::new().with_api_tokens(tokens()));
+
+		assert_eq!(
+			status(&app, "POST", "/lock/door", None).await,
+			StatusCode::UNAUTHORIZED
+		);
+		assert_eq!(
+			status(&app, "POST", "/lock/door", Some("wrong")).await,
+			StatusCode::UNAUTHORIZED
+		);
+		assert_eq!(
+			status(&app, "POST", "/purge", None).await,
+			StatusCode::UNAUTHORIZED
+		);
+		assert_eq!(
+			status(&app, "POST", "/lock/door", Some("old")).await,
+			StatusCode::CREATED
+		);
+		assert_eq!(
+			status(&app, "POST", "/lock/door", Some("new")).await,
+			StatusCode::CREATED
+		);
+	}
+
+	#[tokio::test]
+	async fn reads_are_public_unless_made_private() {
+		let app = router(State::new().with_api_tokens(tokens()));
+
+		assert_eq!(status(&app, "GET", "/usage", None).await, StatusCode::OK);
+		assert_eq!(
+			status(&app, "GET", "/locks/door/state", None).await,
+			StatusCode::OK
+		);
+
+		let app = router(
+			State::new()
+				.with_api_tokens(tokens())
+				.with_private_reads(true),
+		);
+
+		assert_eq!(
+			status(&app, "GET", "/usage", None).await,
+			StatusCode::UNAUTHORIZED
+		);
+		assert_eq!(
+			status(&app, "GET", "/locks/door/state", None).await,
+			StatusCode::UNAUTHORIZED
+		);
+		assert_eq!(
+			status(&app, "GET", "/usage", Some("new")).await,
+			StatusCode::OK
+		);
+	}
+
+	#[tokio::test]
+	async fn everything_is_open_without_tokens() {
+		let app = router(State::new().with_private_reads(true));
+
+		assert_eq!(
+			status(&app, "POST", "/lock/door", None).await,
+			StatusCode::CREATED
+		);
+		assert_eq!(status(&app, "GET", "/usage", None).await, StatusCode::OK);
+	}
+}
@@ -0,0 +1,112 @@
+use std::{fmt, str::FromStr};
+
+use axum::{
+	extract,
+	http::Request,
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+
+use crate::{Error, State};
+
+pub const HEADER: &str = "x-client-version";
+
+// major.minor.patch; missing parts count as 0 and pre-release or build suffixes are ignored
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Version(u64, u64, u64);
+
+impl FromStr for Version {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let core = s
+			.trim()
+			.trim_start_matches('v')
+			.split(['-', '+'])
+			.next()
+			.unwrap_or_default();
+		let parts = core
+			.split('.')
+			.map(|part| part.parse::<u64>())
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|_| format!("invalid version: {}", s))?;
+
+		match parts[..] {
+			[major] => Ok(Self(major, 0, 0)),
+			[major, minor] => Ok(Self(major, minor, 0)),
+			[major, minor, patch] => Ok(Self(major, minor, patch)),
+			_ => Err(format!("invalid version: {}", s)),
+		}
+	}
+}
+
+impl fmt::Display for Version {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}.{}.{}", self.0, self.1, self.2)
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Policy {
+	pub min: Version,
+	pub upgrade_url: Option<String>,
+}
+
+// clients that send no version at all, like scripts and lock controllers, are let through
+pub async fn require<B>(
+	extract::State(state): extract::State<State>,
+	req: Request<B>,
+	next: Next<B>,
+) -> Response {
+	let Some(policy) = &state.client_policy else {
+		return next.run(req).await;
+	};
+	let version = req.headers().get(HEADER).map(|value| {
+		value
+			.to_str()
+			.map_err(|e| e.to_string())?
+			.parse::<Version>()
+	});
+
+	match version {
+		Some(Err(e)) => Error::BadRequest(e).into_response(),
+		Some(Ok(version)) if version < policy.min => Error::UpgradeRequired {
+			min_version: policy.min.to_string(),
+			upgrade_url: policy.upgrade_url.clone(),
+		}
+		.into_response(),
+		_ => next.run(req).await,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(s: &str) -> Result<Version, String> {
+		s.parse()
+	}
+
+	#[test]
+	fn versions_parse_leniently() {
+		assert_eq!(version("1.2.3"), Ok(Version(1, 2, 3)));
+		assert_eq!(version(" v1.2 "), Ok(Version(1, 2, 0)));
+		assert_eq!(version("2"), Ok(Version(2, 0, 0)));
+		assert_eq!(version("1.2.3-beta.1+build.5"), Ok(Version(1, 2, 3)));
+		assert_eq!(Version(1, 2, 0).to_string(), "1.2.0");
+	}
+
+	#[test]
+	fn malformed_versions_are_rejected() {
+		for s in ["", "v", "1.2.3.4", "1..2", "one.two", "-1.0"] {
+			assert!(version(s).is_err(), "{}", s);
+		}
+	}
+
+	#[test]
+	fn versions_compare_numerically() {
+		assert!(version("1.10.0").unwrap() > version("1.9.9").unwrap());
+		assert!(version("2").unwrap() > version("1.99.99").unwrap());
+		assert_eq!(version("1.2").unwrap(), version("1.2.0-rc.1").unwrap());
+	}
+}
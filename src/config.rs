@@ -0,0 +1,193 @@
+use std::{fmt, fs, io, path::Path};
+
+use clap::{parser::ValueSource, ArgMatches, Command};
+use toml::Value;
+
+#[derive(Debug)]
+pub enum Error {
+	Io(io::Error),
+	Malformed(toml::de::Error),
+	UnknownKey(String),
+	InvalidValue(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::Io(e) => write!(f, "io error: {}", e),
+			Error::Malformed(e) => write!(f, "malformed config file: {}", e),
+			Error::UnknownKey(key) => write!(f, "unknown config key '{}'", key),
+			Error::InvalidValue(key) => write!(
+				f,
+				"config key '{}' must be a string, number, boolean or a list of those",
+				key
+			),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Self {
+		Error::Io(e)
+	}
+}
+
+impl From<toml::de::Error> for Error {
+	fn from(e: toml::de::Error) -> Self {
+		Error::Malformed(e)
+	}
+}
+
+fn scalar(key: &str, value: Value) -> Result<String, Error> {
+	match value {
+		Value::String(value) => Ok(value),
+		Value::Integer(value) => Ok(value.to_string()),
+		Value::Float(value) => Ok(value.to_string()),
+		_ => Err(Error::InvalidValue(key.to_string())),
+	}
+}
+
+// turns a toml file into extra arguments for `command`, so that the file goes through the same
+// parsing and validation as flags; keys are long option names (`database-url` or `database_url`)
+// and options already given on the command line or in the environment are left alone
+pub fn file_args(
+	command: &Command,
+	matches: &ArgMatches,
+	path: &Path,
+) -> Result<Vec<String>, Error> {
+	let table: toml::Table = toml::from_str(&fs::read_to_string(path)?)?;
+	let mut args = Vec::new();
+
+	for (key, value) in table {
+		let long = key.replace('_', "-");
+		let arg = command
+			.get_arguments()
+			.find(|arg| arg.get_long() == Some(long.as_str()) && long != "config")
+			.ok_or_else(|| Error::UnknownKey(key.clone()))?;
+
+		if matches!(
+			matches.value_source(arg.get_id().as_str()),
+			Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+		) {
+			continue;
+		}
+
+		let flag = format!("--{}", long);
+
+		match value {
+			Value::Boolean(true) => args.push(flag),
+			Value::Boolean(false) => {}
+			Value::Array(values) => {
+				for value in values {
+					args.push(format!("{}={}", flag, scalar(&key, value)?));
+				}
+			}
+			value => args.push(format!("{}={}", flag, scalar(&key, value)?)),
+		}
+	}
+
+	Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{env, process};
+
+	use clap::{Arg, ArgAction};
+
+	use super::*;
+
+	fn command() -> Command {
+		Command::new("serve")
+			.arg(Arg::new("config").long("config"))
+			.arg(Arg::new("port").long("port"))
+			.arg(Arg::new("database_url").long("database-url"))
+			.arg(
+				Arg::new("api_tokens")
+					.long("api-token")
+					.action(ArgAction::Append),
+			)
+			.arg(
+				Arg::new("reuse_port")
+					.long("reuse-port")
+					.action(ArgAction::SetTrue),
+			)
+			.arg(
+				Arg::new("strict_json")
+					.long("strict-json")
+					.action(ArgAction::SetTrue),
+			)
+	}
+
+	fn args(cli: &[&str], toml: &str) -> Result<Vec<String>, Error> {
+		let path = env::temp_dir().join(format!(
+			"touchid-config-{}-{:?}.toml",
+			process::id(),
+			std::thread::current().id()
+		));
+		fs::write(&path, toml).unwrap();
+
+		let command = command();
+		let matches = command
+			.clone()
+			.try_get_matches_from(["serve"].iter().chain(cli))
+			.unwrap();
+		let args = file_args(&command, &matches, &path);
+		fs::remove_file(&path).unwrap();
+
+		args
+	}
+
+	#[test]
+	fn keys_become_flags() {
+		let args = args(
+			&[],
+			r#"
+			port = 3000
+			database_url = "sqlite:locks.db"
+			api-token = ["a", "b"]
+			reuse-port = true
+			strict-json = false
+			"#,
+		)
+		.unwrap();
+
+		assert_eq!(
+			args,
+			[
+				"--api-token=a",
+				"--api-token=b",
+				"--database-url=sqlite:locks.db",
+				"--port=3000",
+				"--reuse-port",
+			]
+		);
+	}
+
+	#[test]
+	fn the_command_line_wins() {
+		let args = args(&["--port", "4000"], "port = 3000\nreuse-port = true").unwrap();
+
+		assert_eq!(args, ["--reuse-port"]);
+	}
+
+	#[test]
+	fn bad_files_are_rejected() {
+		assert!(matches!(args(&[], "nope = 1"), Err(Error::UnknownKey(key)) if key == "nope"));
+		assert!(matches!(
+			args(&[], "config = \"x\""),
+			Err(Error::UnknownKey(_))
+		));
+		assert!(matches!(args(&[], "port = "), Err(Error::Malformed(_))));
+		assert!(matches!(
+			args(&[], "port = { value = 1 }"),
+			Err(Error::InvalidValue(key)) if key == "port"
+		));
+		assert!(matches!(
+			args(&[], "api-token = [[\"a\"]]"),
+			Err(Error::InvalidValue(_))
+		));
+	}
+}
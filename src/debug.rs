@@ -0,0 +1,155 @@
+use std::fs;
+#[cfg(feature = "pprof")]
+use std::time::Duration;
+
+use axum::{extract, middleware, routing::get, Router};
+#[cfg(feature = "pprof")]
+use axum::{
+	http::header,
+	response::{IntoResponse, Response},
+};
+use serde::{self, Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+#[cfg(feature = "pprof")]
+use crate::params::Query;
+use crate::{admin, json::Reply, quota, Error, State};
+
+#[cfg(feature = "pprof")]
+const DEFAULT_SECONDS: u64 = 10;
+#[cfg(feature = "pprof")]
+const MAX_SECONDS: u64 = 60;
+#[cfg(feature = "pprof")]
+const FREQUENCY: i32 = 99;
+
+// mounted alongside the admin routes
+pub fn router(state: State) -> Router<State> {
+	let router = Router::new().route("/memory", get(memory));
+	#[cfg(feature = "pprof")]
+	let router = router.route("/pprof/profile", get(profile));
+
+	router.route_layer(middleware::from_fn_with_state(state, admin::require_admin))
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct Allocator {
+	pub allocated: usize,
+	pub active: usize,
+	pub resident: usize,
+	pub mapped: usize,
+	pub retained: usize,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct Memory {
+	pub resident_bytes: Option<u64>,
+	// only reported by builds with the `jemalloc` feature
+	pub allocator: Option<Allocator>,
+	pub locks: quota::Report,
+}
+
+// VmRSS from procfs; None on platforms without it
+pub fn resident_bytes() -> Option<u64> {
+	fs::read_to_string("/proc/self/status")
+		.ok()?
+		.lines()
+		.find_map(|line| line.strip_prefix("VmRSS:"))
+		.and_then(|value| value.trim().strip_suffix("kB"))
+		.and_then(|kb| kb.trim().parse::<u64>().ok())
+		.map(|kb| kb * 1024)
+}
+
+#[cfg(feature = "jemalloc")]
+fn allocator() -> Option<Allocator> {
+	use tikv_jemalloc_ctl::{epoch, stats};
+
+	// stats are cached by jemalloc until the epoch is advanced
+	epoch::advance().ok()?;
+
+	Some(Allocator {
+		allocated: stats::allocated::read().ok()?,
+		active: stats::active::read().ok()?,
+		resident: stats::resident::read().ok()?,
+		mapped: stats::mapped::read().ok()?,
+		retained: stats::retained::read().ok()?,
+	})
+}
+
+#[cfg(not(feature = "jemalloc"))]
+fn allocator() -> Option<Allocator> {
+	None
+}
+
+#[utoipa::path(
+	get,
+	path = "/debug/memory",
+	tag = "admin",
+	responses((status = 200, description = "process, allocator and lock store memory use", body = Memory)),
+	security(("admin_token" = [])),
+)]
+pub async fn memory(extract::State(state): extract::State<State>) -> Result<Reply<Memory>, Error> {
+	Ok(state.json.reply(Memory {
+		resident_bytes: resident_bytes(),
+		allocator: allocator(),
+		locks: state.store.usage().await?,
+	}))
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[serde(crate = "self::serde")]
+#[into_params(parameter_in = Query)]
+pub struct ProfileParams {
+	// how long to sample for; 10 seconds by default and at most 60
+	pub seconds: Option<u64>,
+}
+
+// samples the whole process for the requested duration and returns an svg flamegraph
+#[cfg(feature = "pprof")]
+#[utoipa::path(
+	get,
+	path = "/debug/pprof/profile",
+	tag = "admin",
+	params(ProfileParams),
+	responses(
+		(status = 200, description = "an svg flamegraph", content_type = "image/svg+xml", body = String),
+		(status = 409, description = "another profile is running", body = crate::error::ErrorBody),
+	),
+	security(("admin_token" = [])),
+)]
+pub async fn profile(Query(params): Query<ProfileParams>) -> Result<Response, Error> {
+	let seconds = params
+		.seconds
+		.unwrap_or(DEFAULT_SECONDS)
+		.clamp(1, MAX_SECONDS);
+
+	let svg = tokio::task::spawn_blocking(move || {
+		// fails while another profile is running
+		let guard = pprof::ProfilerGuardBuilder::default()
+			.frequency(FREQUENCY)
+			.blocklist(&["libc", "libgcc", "pthread", "vdso"])
+			.build()
+			.map_err(|_| Error::Conflict("a profile is already running".to_string()))?;
+
+		std::thread::sleep(Duration::from_secs(seconds));
+
+		let report = guard.report().build().map_err(|e| {
+			tracing::error!(error = %e, "failed to build a profile");
+
+			Error::Internal
+		})?;
+		let mut svg = Vec::new();
+		report.flamegraph(&mut svg).map_err(|e| {
+			tracing::error!(error = %e, "failed to render a flamegraph");
+
+			Error::Internal
+		})?;
+
+		Ok::<_, Error>(svg)
+	})
+	.await
+	.map_err(|_| Error::Internal)??;
+
+	Ok(([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response())
+}
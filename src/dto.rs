@@ -0,0 +1,144 @@
+// wire formats are kept apart from the internal model: a version's shapes never change once released,
+// new optional fields are added with #[serde(default)] and anything incompatible goes into a new module
+
+pub mod v1 {
+	use serde::{self, Deserialize, Serialize};
+	use utoipa::ToSchema;
+
+	use crate::lock::Lock;
+
+	#[derive(Deserialize, Clone, PartialEq, Debug, ToSchema)]
+	#[serde(crate = "self::serde")]
+	pub struct LockRequest {
+		pub token: String,
+		// seconds the lock stays valid; the server default applies when omitted
+		#[serde(default)]
+		pub ttl: Option<u64>,
+		// successful verifications before the lock goes away; unlimited when omitted
+		#[serde(default)]
+		pub max_uses: Option<u32>,
+	}
+
+	#[derive(Serialize, Clone, PartialEq, Debug, ToSchema)]
+	#[serde(crate = "self::serde")]
+	pub struct LockResponse {
+		pub token: String,
+	}
+
+	#[derive(Serialize, Clone, PartialEq, Debug, ToSchema)]
+	#[serde(crate = "self::serde")]
+	pub struct IssueResponse {
+		pub id: String,
+		pub token: String,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub expires_at: Option<u64>,
+	}
+
+	#[derive(Deserialize, Clone, PartialEq, Debug, ToSchema)]
+	#[serde(crate = "self::serde")]
+	pub struct VerifyRequest {
+		pub id: String,
+		pub token: String,
+	}
+
+	#[derive(Serialize, Clone, PartialEq, Debug, ToSchema)]
+	#[serde(crate = "self::serde")]
+	pub struct VerifyResponse {
+		pub valid: bool,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub uses_left: Option<u32>,
+	}
+
+	impl From<Lock> for LockResponse {
+		fn from(lock: Lock) -> Self {
+			Self { token: lock.token }
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::v1::*;
+
+	// bodies as clients sent them before ttl and max_uses existed
+	const LOCK_V1_0: &str = r#"{"token":"tok"}"#;
+	const VERIFY_V1_0: &str = r#"{"id":"door","token":"tok"}"#;
+
+	#[test]
+	fn old_lock_request_still_parses() {
+		let req: LockRequest = serde_json::from_str(LOCK_V1_0).unwrap();
+
+		assert_eq!(
+			req,
+			LockRequest {
+				token: "tok".to_string(),
+				ttl: None,
+				max_uses: None,
+			}
+		);
+	}
+
+	#[test]
+	fn current_lock_request_parses() {
+		let req: LockRequest =
+			serde_json::from_str(r#"{"token":"tok","ttl":60,"max_uses":3}"#).unwrap();
+
+		assert_eq!(req.ttl, Some(60));
+		assert_eq!(req.max_uses, Some(3));
+	}
+
+	#[test]
+	fn old_verify_request_still_parses() {
+		let req: VerifyRequest = serde_json::from_str(VERIFY_V1_0).unwrap();
+
+		assert_eq!(
+			req,
+			VerifyRequest {
+				id: "door".to_string(),
+				token: "tok".to_string(),
+			}
+		);
+	}
+
+	#[test]
+	fn responses_keep_their_shape() {
+		let lock = LockResponse {
+			token: "tok".to_string(),
+		};
+		let issued = IssueResponse {
+			id: "door".to_string(),
+			token: "tok".to_string(),
+			expires_at: None,
+		};
+		let expiring = IssueResponse {
+			expires_at: Some(1_700_000_000),
+			..issued.clone()
+		};
+		let verified = VerifyResponse {
+			valid: true,
+			uses_left: None,
+		};
+		let limited = VerifyResponse {
+			valid: true,
+			uses_left: Some(2),
+		};
+
+		assert_eq!(serde_json::to_string(&lock).unwrap(), r#"{"token":"tok"}"#);
+		assert_eq!(
+			serde_json::to_string(&issued).unwrap(),
+			r#"{"id":"door","token":"tok"}"#
+		);
+		assert_eq!(
+			serde_json::to_string(&expiring).unwrap(),
+			r#"{"id":"door","token":"tok","expires_at":1700000000}"#
+		);
+		assert_eq!(
+			serde_json::to_string(&verified).unwrap(),
+			r#"{"valid":true}"#
+		);
+		assert_eq!(
+			serde_json::to_string(&limited).unwrap(),
+			r#"{"valid":true,"uses_left":2}"#
+		);
+	}
+}
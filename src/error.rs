@@ -0,0 +1,345 @@
+use std::{fmt, time::Duration};
+
+use axum::{
+	body::{self, Full},
+	extract,
+	http::{header, HeaderMap, HeaderValue, Request, StatusCode},
+	middleware::Next,
+	response::{IntoResponse, Response},
+	Json,
+};
+use serde::{self, Serialize};
+use serde_json::{json, Value};
+use utoipa::ToSchema;
+
+use crate::{json, validate::FieldError};
+
+// every error is answered with {"error": <message>, "code": <code>, "details": {..}}, or as an
+// rfc 7807 problem document when the client asks for one; clients should branch on `code`,
+// which never changes once released, while messages are for humans
+#[derive(Clone, Debug)]
+pub enum Error {
+	NotFound,
+	NoRoute,
+	MethodNotAllowed,
+	BadRequest(String),
+	InvalidBody(String),
+	UnsupportedMediaType(String),
+	UnknownFields(Vec<String>),
+	Validation(Vec<FieldError>),
+	Unauthorized,
+	Conflict(String),
+	RateLimited(Duration),
+	InsufficientStorage,
+	Storage,
+	Internal,
+	Unavailable(String),
+	UpgradeRequired {
+		min_version: String,
+		upgrade_url: Option<String>,
+	},
+}
+
+impl Error {
+	pub fn status(&self) -> StatusCode {
+		match self {
+			Error::NotFound => StatusCode::GONE,
+			Error::NoRoute => StatusCode::NOT_FOUND,
+			Error::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+			Error::BadRequest(_) | Error::UnknownFields(_) => StatusCode::BAD_REQUEST,
+			Error::InvalidBody(_) | Error::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+			Error::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+			Error::Unauthorized => StatusCode::UNAUTHORIZED,
+			Error::Conflict(_) => StatusCode::CONFLICT,
+			Error::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+			Error::InsufficientStorage => StatusCode::INSUFFICIENT_STORAGE,
+			Error::Storage | Error::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+			Error::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+			Error::UpgradeRequired { .. } => StatusCode::UPGRADE_REQUIRED,
+		}
+	}
+
+	pub fn code(&self) -> &'static str {
+		match self {
+			Error::NotFound => "not_found",
+			Error::NoRoute => "no_route",
+			Error::MethodNotAllowed => "method_not_allowed",
+			Error::BadRequest(_) => "bad_request",
+			Error::InvalidBody(_) => "invalid_body",
+			Error::UnsupportedMediaType(_) => "unsupported_media_type",
+			Error::UnknownFields(_) => "unknown_fields",
+			Error::Validation(_) => "validation_failed",
+			Error::Unauthorized => "unauthorized",
+			Error::Conflict(_) => "conflict",
+			Error::RateLimited(_) => "rate_limited",
+			Error::InsufficientStorage => "insufficient_storage",
+			Error::Storage => "storage_error",
+			Error::Internal => "internal_error",
+			Error::Unavailable(_) => "unavailable",
+			Error::UpgradeRequired { .. } => "upgrade_required",
+		}
+	}
+
+	// field paths name the fields in the casing the client uses
+	fn details(&self, case: json::Case) -> Option<Value> {
+		match self {
+			Error::UnknownFields(fields) => Some(json!({ "fields": fields })),
+			Error::Validation(errors) if case == json::Case::Camel => {
+				let errors = errors
+					.iter()
+					.map(|error| FieldError {
+						field: json::path_to_camel(&error.field),
+						message: error.message.clone(),
+					})
+					.collect::<Vec<_>>();
+
+				Some(json!({ "errors": errors }))
+			}
+			Error::Validation(errors) => Some(json!({ "errors": errors })),
+			Error::RateLimited(retry_after) => {
+				Some(json!({ "retry_after": retry_after.as_secs() }))
+			}
+			Error::UpgradeRequired {
+				min_version,
+				upgrade_url,
+			} => Some(json!({
+				"min_version": min_version,
+				"upgrade_url": upgrade_url,
+			})),
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::NotFound => write!(f, "not found"),
+			Error::NoRoute => write!(f, "no such route"),
+			Error::MethodNotAllowed => write!(f, "method not allowed on this route"),
+			Error::BadRequest(message)
+			| Error::Conflict(message)
+			| Error::InvalidBody(message)
+			| Error::UnsupportedMediaType(message)
+			| Error::Unavailable(message) => write!(f, "{}", message),
+			Error::UnknownFields(_) => write!(f, "unknown fields in request body"),
+			Error::Validation(_) => write!(f, "request failed validation"),
+			Error::Unauthorized => write!(f, "missing or invalid bearer token"),
+			Error::RateLimited(_) => write!(f, "too many requests"),
+			Error::InsufficientStorage => write!(f, "storage limit reached"),
+			Error::Storage => write!(f, "storage failure"),
+			Error::Internal => write!(f, "internal error"),
+			Error::UpgradeRequired { .. } => write!(f, "client upgrade required"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+// answers requests no route matches
+pub async fn fallback() -> Error {
+	Error::NoRoute
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct ErrorBody {
+	pub error: String,
+	pub code: &'static str,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[schema(value_type = Option<Object>)]
+	pub details: Option<Value>,
+}
+
+impl IntoResponse for Error {
+	fn into_response(self) -> Response {
+		let body = ErrorBody {
+			error: self.to_string(),
+			code: self.code(),
+			details: self.details(json::Case::Snake),
+		};
+
+		let mut res = (self.status(), Json(body)).into_response();
+
+		if let Error::RateLimited(retry_after) = self {
+			res.headers_mut()
+				.insert(header::RETRY_AFTER, retry_after.as_secs().into());
+		}
+
+		res.extensions_mut().insert(Problem(self));
+
+		res
+	}
+}
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+// lets `negotiate` re-render an error that has already been turned into a response
+#[derive(Clone, Debug)]
+struct Problem(Error);
+
+fn wants_problem(headers: &HeaderMap) -> bool {
+	headers
+		.get_all(header::ACCEPT)
+		.iter()
+		.filter_map(|value| value.to_str().ok())
+		.flat_map(|value| value.split(','))
+		.any(|range| range.split(';').next().unwrap_or_default().trim() == PROBLEM_JSON)
+}
+
+// renders error bodies in the configured json format, or as problem documents when Accept lists
+// application/problem+json
+pub async fn negotiate<B>(
+	extract::State(format): extract::State<json::Format>,
+	req: Request<B>,
+	next: Next<B>,
+) -> Response {
+	let wants_problem = wants_problem(req.headers());
+	let instance = req.uri().path().to_string();
+	let res = next.run(req).await;
+	let (mut parts, body) = res.into_parts();
+	// axum answers methods a route does not serve on its own, with an empty body
+	let error = match parts.extensions.remove::<Problem>() {
+		Some(Problem(error)) => error,
+		None if parts.status == StatusCode::METHOD_NOT_ALLOWED => Error::MethodNotAllowed,
+		None => return Response::from_parts(parts, body),
+	};
+	let details = error.details(format.case);
+	let (content_type, rendered) = if wants_problem {
+		let mut doc = json!({
+			"type": format!("urn:touchid:error:{}", error.code()),
+			"title": parts.status.canonical_reason().unwrap_or_default(),
+			"status": parts.status.as_u16(),
+			"detail": error.to_string(),
+			"instance": instance,
+			"code": error.code(),
+		});
+
+		if let Some(details) = details {
+			doc["details"] = details;
+		}
+
+		(PROBLEM_JSON, format.render(&doc))
+	} else {
+		let body = ErrorBody {
+			error: error.to_string(),
+			code: error.code(),
+			details,
+		};
+
+		("application/json", format.render(&body))
+	};
+	let Ok(rendered) = rendered else {
+		return Response::from_parts(parts, body);
+	};
+
+	parts
+		.headers
+		.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+	parts.headers.remove(header::CONTENT_LENGTH);
+
+	Response::from_parts(parts, body::boxed(Full::from(rendered)))
+}
+
+#[cfg(test)]
+mod tests {
+	use axum::{body::Body, middleware, routing::get, Router};
+	use tower::ServiceExt;
+
+	use super::*;
+	use crate::json::{Case, Format};
+
+	async fn render(format: Format, accept: &str) -> (HeaderMap, String) {
+		let app = Router::new()
+			.route(
+				"/limited",
+				get(|| async { Error::RateLimited(Duration::from_secs(60)) }),
+			)
+			.layer(middleware::from_fn_with_state(format, negotiate));
+		let req = Request::get("/limited")
+			.header(header::ACCEPT, accept)
+			.body(Body::empty())
+			.unwrap();
+		let res = app.oneshot(req).await.unwrap();
+		let headers = res.headers().clone();
+		let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+
+		(headers, String::from_utf8(body.to_vec()).unwrap())
+	}
+
+	#[tokio::test]
+	async fn errors_follow_the_configured_format() {
+		let (_, body) = render(Format::default(), "application/json").await;
+		assert_eq!(
+			body,
+			r#"{"error":"too many requests","code":"rate_limited","details":{"retry_after":60}}"#
+		);
+
+		let camel = Format {
+			case: Case::Camel,
+			pretty: false,
+		};
+		let (headers, body) = render(camel, "application/json").await;
+		assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+		assert_eq!(headers[header::RETRY_AFTER], "60");
+		assert_eq!(
+			body,
+			r#"{"error":"too many requests","code":"rate_limited","details":{"retryAfter":60}}"#
+		);
+
+		let (headers, body) = render(camel, PROBLEM_JSON).await;
+		assert_eq!(headers[header::CONTENT_TYPE], PROBLEM_JSON);
+		assert!(body.contains(r#""details":{"retryAfter":60}"#), "{}", body);
+
+		let pretty = Format {
+			case: Case::Snake,
+			pretty: true,
+		};
+		let (_, body) = render(pretty, "application/json").await;
+		assert!(body.starts_with("{\n  \"error\""), "{}", body);
+	}
+
+	async fn code(app: &Router, method: &str, uri: &str) -> (StatusCode, String) {
+		let req = Request::builder()
+			.method(method)
+			.uri(uri)
+			.header(header::AUTHORIZATION, "Bearer admin")
+			.body(Body::empty())
+			.unwrap();
+		let res = app.clone().oneshot(req).await.unwrap();
+		let status = res.status();
+		let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+		let body: Value = serde_json::from_slice(&body).unwrap();
+
+		(status, body["code"].as_str().unwrap().to_string())
+	}
+
+	#[tokio::test]
+	async fn routing_and_parameter_errors_carry_a_code() {
+		let app = crate::router(crate::State::new().with_admin_token(Some("admin".to_string())));
+
+		assert_eq!(
+			code(&app, "GET", "/nope").await,
+			(StatusCode::NOT_FOUND, "no_route".to_string())
+		);
+		assert_eq!(
+			code(&app, "DELETE", "/version").await,
+			(
+				StatusCode::METHOD_NOT_ALLOWED,
+				"method_not_allowed".to_string()
+			)
+		);
+		assert_eq!(
+			code(&app, "GET", "/locks/door/analytics?format=xml").await,
+			(StatusCode::BAD_REQUEST, "bad_request".to_string())
+		);
+		assert_eq!(
+			code(&app, "DELETE", "/admin/announcements/abc").await,
+			(StatusCode::BAD_REQUEST, "bad_request".to_string())
+		);
+		assert_eq!(
+			code(&app, "GET", "/admin/nope").await,
+			(StatusCode::NOT_FOUND, "no_route".to_string())
+		);
+	}
+}
@@ -0,0 +1,210 @@
+use axum::{extract, http::StatusCode};
+use serde::{self, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+use crate::{
+	json::{Body, Reply},
+	validate::{Errors, Validate},
+	Error, State,
+};
+
+const MAX_BATCH: usize = 100;
+const MAX_NAME_LEN: usize = 64;
+const MAX_PROPERTIES: usize = 32;
+const MAX_VALUE_LEN: usize = 256;
+// events are meant to be anonymous; properties under these names are refused outright
+const PERSONAL_KEYS: &[&str] = &[
+	"email",
+	"ip",
+	"ip_address",
+	"name",
+	"phone",
+	"token",
+	"user_id",
+	"username",
+];
+
+// matched whatever the casing, so that user_id, userId and User-ID are all caught
+fn is_personal(key: &str) -> bool {
+	let normalize = |key: &str| {
+		key.chars()
+			.filter(|c| !matches!(c, '_' | '-'))
+			.map(|c| c.to_ascii_lowercase())
+			.collect::<String>()
+	};
+	let key = normalize(key);
+
+	PERSONAL_KEYS
+		.iter()
+		.any(|personal| normalize(personal) == key)
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct Event {
+	pub name: String,
+	// unix seconds as recorded on the device; not trusted for anything but reporting
+	#[serde(default)]
+	pub timestamp: Option<u64>,
+	#[serde(default)]
+	pub properties: Map<String, Value>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct TrackRequest {
+	pub events: Vec<Event>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct TrackResponse {
+	pub accepted: usize,
+	pub sampled_out: usize,
+}
+
+// where accepted events go; implementations must not block the request
+pub trait EventSink: Send + Sync {
+	fn send(&self, events: Vec<Event>);
+}
+
+// writes every event to the `touchid::events` log target, to be shipped on with the logs
+#[derive(Default, Debug)]
+pub struct LogSink;
+
+impl EventSink for LogSink {
+	fn send(&self, events: Vec<Event>) {
+		for event in events {
+			let properties = Value::Object(event.properties).to_string();
+
+			tracing::info!(
+				target: "touchid::events",
+				name = %event.name,
+				timestamp = event.timestamp,
+				properties = %properties,
+				"event"
+			);
+		}
+	}
+}
+
+impl Validate for TrackRequest {
+	fn validate(&self, errors: &mut Errors) {
+		errors.check(!self.events.is_empty(), "events", "must not be empty");
+		errors.check(
+			self.events.len() <= MAX_BATCH,
+			"events",
+			format!("must hold at most {} events", MAX_BATCH),
+		);
+
+		for (i, event) in self.events.iter().enumerate() {
+			let field = |name: &str| format!("events[{}].{}", i, name);
+
+			errors.check(
+				!event.name.is_empty()
+					&& event.name.len() <= MAX_NAME_LEN
+					&& event.name.chars().all(|c| {
+						c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '.')
+					}),
+				field("name"),
+				format!(
+					"must be 1 to {} characters of a-z, 0-9, '_' and '.'",
+					MAX_NAME_LEN
+				),
+			);
+			errors.check(
+				event.properties.len() <= MAX_PROPERTIES,
+				field("properties"),
+				format!("must hold at most {} entries", MAX_PROPERTIES),
+			);
+
+			for (key, value) in &event.properties {
+				// quoted, as property names are the client's own and keep their casing
+				let field = || field(&format!("properties[{}]", Value::from(key.as_str())));
+
+				errors.check(
+					!is_personal(key),
+					field(),
+					"looks like personal data, which events must not carry",
+				);
+				errors.check(
+					match value {
+						Value::String(value) => value.len() <= MAX_VALUE_LEN,
+						Value::Array(_) | Value::Object(_) => false,
+						_ => true,
+					},
+					field(),
+					format!(
+						"must be a number, bool, null or a string of at most {} bytes",
+						MAX_VALUE_LEN
+					),
+				);
+			}
+		}
+	}
+}
+
+// neither the caller's address nor any other request metadata is attached to events
+#[utoipa::path(
+	post,
+	path = "/events/track",
+	tag = "events",
+	request_body = TrackRequest,
+	responses(
+		(status = 202, description = "the events were queued", body = TrackResponse),
+		(status = 422, description = "the request failed validation", body = crate::error::ErrorBody),
+	),
+	security((), ("api_token" = [])),
+)]
+pub async fn track(
+	extract::State(state): extract::State<State>,
+	Body(req): Body<TrackRequest>,
+) -> Result<(StatusCode, Reply<TrackResponse>), Error> {
+	let mut errors = Errors::default();
+	req.validate(&mut errors);
+	errors.into_result()?;
+
+	let total = req.events.len();
+	let events = req
+		.events
+		.into_iter()
+		.filter(|_| rand::random::<f64>() < state.event_sample_rate)
+		.collect::<Vec<_>>();
+	let accepted = events.len();
+
+	if !events.is_empty() {
+		state.events.send(events);
+	}
+
+	Ok((
+		StatusCode::ACCEPTED,
+		state.json.reply(TrackResponse {
+			accepted,
+			sampled_out: total - accepted,
+		}),
+	))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn personal_keys_are_caught_in_any_casing() {
+		for key in [
+			"user_id",
+			"userId",
+			"user-id",
+			"UserID",
+			"IP_Address",
+			"ipAddress",
+		] {
+			assert!(is_personal(key), "{}", key);
+		}
+
+		for key in ["screen_name", "users", "ipv6"] {
+			assert!(!is_personal(key), "{}", key);
+		}
+	}
+}
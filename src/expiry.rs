@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use crate::{lock, prometheus, State};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+// sweeps between two measurements of the store for the metrics, about a minute
+const USAGE_SWEEPS: u64 = 12;
+
+// expired locks already read as absent; the sweep reclaims their space and wakes state pollers.
+// it also drops occupancy stats past their retention and status page clients whose rate limit
+// window has passed, and refreshes the store size gauges
+pub fn spawn(state: State) {
+	tokio::spawn(async move {
+		let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+		interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+		for sweep in 0u64.. {
+			interval.tick().await;
+
+			let now = lock::now();
+			state.occupancy.prune(now);
+			state.status.prune();
+
+			match state.store.remove_expired(now).await {
+				Ok(ids) => {
+					for id in &ids {
+						state.lock_states.record(id, false);
+					}
+
+					if !ids.is_empty() {
+						tracing::debug!(count = ids.len(), "expired locks removed");
+					}
+				}
+				Err(e) => tracing::error!(error = ?e, "expiry sweep failed"),
+			}
+
+			if sweep % USAGE_SWEEPS == 0 {
+				prometheus::record_usage(&state).await;
+			}
+		}
+	});
+}
@@ -0,0 +1,105 @@
+use std::sync::{
+	atomic::{AtomicBool, Ordering},
+	Arc,
+};
+
+use axum::{
+	extract,
+	http::{Request, StatusCode},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+
+use crate::{Error, State};
+
+// holds the app back until its store is open; everything but /healthz is refused until then
+#[derive(Default, Debug)]
+pub struct Gate {
+	open: AtomicBool,
+}
+
+impl Gate {
+	pub fn open(&self) {
+		self.open.store(true, Ordering::Release);
+	}
+}
+
+pub async fn gate<B>(
+	extract::State(gate): extract::State<Arc<Gate>>,
+	req: Request<B>,
+	next: Next<B>,
+) -> Response {
+	if gate.open.load(Ordering::Acquire) || req.uri().path() == "/healthz" {
+		next.run(req).await
+	} else {
+		Error::Unavailable("starting up".to_string()).into_response()
+	}
+}
+
+// liveness: the process is up and answering, whatever state the store is in
+#[utoipa::path(
+	get,
+	path = "/healthz",
+	tag = "service",
+	responses((status = 200, description = "the process is alive")),
+)]
+pub async fn healthz() -> StatusCode {
+	StatusCode::OK
+}
+
+// readiness: the store is open and answers
+#[utoipa::path(
+	get,
+	path = "/readyz",
+	tag = "service",
+	responses(
+		(status = 200, description = "ready to take traffic"),
+		(status = 503, description = "starting up or the store is unreachable", body = crate::error::ErrorBody),
+	),
+)]
+pub async fn readyz(extract::State(state): extract::State<State>) -> Result<StatusCode, Error> {
+	match state.store.ping().await {
+		Ok(()) => Ok(StatusCode::OK),
+		Err(e) => {
+			tracing::warn!(error = ?e, "readiness check failed");
+
+			Err(Error::Unavailable("storage unreachable".to_string()))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use axum::body::Body;
+	use tower::ServiceExt;
+
+	use super::*;
+	use crate::router;
+
+	async fn status(app: &axum::Router, path: &str) -> StatusCode {
+		let req = Request::get(path).body(Body::empty()).unwrap();
+
+		app.clone().oneshot(req).await.unwrap().status()
+	}
+
+	#[tokio::test]
+	async fn only_liveness_answers_until_the_gate_opens() {
+		let gate = Arc::new(Gate::default());
+		let app = router(State::new().with_startup_gate(gate.clone()));
+
+		assert_eq!(status(&app, "/healthz").await, StatusCode::OK);
+		assert_eq!(
+			status(&app, "/readyz").await,
+			StatusCode::SERVICE_UNAVAILABLE
+		);
+		assert_eq!(
+			status(&app, "/version").await,
+			StatusCode::SERVICE_UNAVAILABLE
+		);
+
+		gate.open();
+
+		assert_eq!(status(&app, "/readyz").await, StatusCode::OK);
+		assert_eq!(status(&app, "/version").await, StatusCode::OK);
+	}
+}
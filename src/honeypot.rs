@@ -0,0 +1,195 @@
+use std::{
+	collections::VecDeque,
+	net::{IpAddr, SocketAddr},
+	sync::Mutex,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+	extract::{self, ConnectInfo},
+	http::{header, HeaderMap, StatusCode, Uri},
+	routing::any,
+	Router,
+};
+use serde::{self, Serialize};
+use utoipa::ToSchema;
+
+use crate::{json::Reply, State};
+
+// paths no legitimate client of this api ever requests
+pub const DECOYS: &[&str] = &[
+	"/wp-login.php",
+	"/wp-admin",
+	"/.env",
+	"/.git/config",
+	"/phpmyadmin",
+	"/admin.php",
+	"/users/0/password",
+];
+
+const CAPACITY: usize = 1024;
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct Hit {
+	pub at: u64,
+	pub ip: Option<String>,
+	pub path: String,
+	pub user_agent: Option<String>,
+}
+
+// most recent hits, oldest dropped first once full
+#[derive(Debug)]
+pub struct Hits {
+	hits: Mutex<VecDeque<Hit>>,
+	capacity: usize,
+}
+
+impl Default for Hits {
+	fn default() -> Self {
+		Self::new(CAPACITY)
+	}
+}
+
+impl Hits {
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			hits: Mutex::new(VecDeque::with_capacity(capacity)),
+			capacity,
+		}
+	}
+
+	pub fn record(&self, hit: Hit) {
+		let mut hits = self.hits.lock().unwrap();
+
+		if hits.len() == self.capacity {
+			hits.pop_front();
+		}
+
+		hits.push_back(hit);
+	}
+
+	pub fn list(&self) -> Vec<Hit> {
+		self.hits.lock().unwrap().iter().cloned().collect()
+	}
+}
+
+pub fn router() -> Router<State> {
+	DECOYS
+		.iter()
+		.fold(Router::new(), |router, path| router.route(path, any(trap)))
+}
+
+// the peer address, unless it is a trusted proxy: then the right-most X-Forwarded-For entry that was
+// not added by a trusted proxy, since anything left of that may have been made up by the client
+pub(crate) fn client_ip(
+	headers: &HeaderMap,
+	peer: Option<SocketAddr>,
+	trusted: &[IpAddr],
+) -> Option<IpAddr> {
+	let peer = peer?.ip();
+
+	if !trusted.contains(&peer) {
+		return Some(peer);
+	}
+
+	let mut client = peer;
+
+	for value in headers.get_all("x-forwarded-for") {
+		let Ok(value) = value.to_str() else {
+			return Some(client);
+		};
+
+		for entry in value.split(',').rev() {
+			let Ok(ip) = entry.trim().parse::<IpAddr>() else {
+				return Some(client);
+			};
+
+			client = ip;
+
+			if !trusted.contains(&ip) {
+				return Some(ip);
+			}
+		}
+	}
+
+	Some(client)
+}
+
+pub async fn trap(
+	extract::State(state): extract::State<State>,
+	peer: Option<ConnectInfo<SocketAddr>>,
+	headers: HeaderMap,
+	uri: Uri,
+) -> StatusCode {
+	let hit = Hit {
+		at: SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs())
+			.unwrap_or_default(),
+		ip: client_ip(
+			&headers,
+			peer.map(|ConnectInfo(peer)| peer),
+			&state.trusted_proxies,
+		)
+		.map(|ip| ip.to_string()),
+		path: uri.path().to_string(),
+		user_agent: headers
+			.get(header::USER_AGENT)
+			.and_then(|value| value.to_str().ok())
+			.map(str::to_string),
+	};
+
+	tracing::warn!(
+		ip = hit.ip.as_deref().unwrap_or("-"),
+		path = %hit.path,
+		user_agent = hit.user_agent.as_deref().unwrap_or("-"),
+		"honeypot hit"
+	);
+	state.honeypot.record(hit);
+
+	StatusCode::NOT_FOUND
+}
+
+#[utoipa::path(
+	get,
+	path = "/admin/honeypot",
+	operation_id = "honeypot_hits",
+	tag = "admin",
+	responses((status = 200, description = "recent requests for decoy paths", body = Vec<Hit>)),
+	security(("admin_token" = [])),
+)]
+pub async fn hits(extract::State(state): extract::State<State>) -> Reply<Vec<Hit>> {
+	state.json.reply(state.honeypot.list())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn forwarded(value: &str) -> HeaderMap {
+		let mut headers = HeaderMap::new();
+		headers.insert("x-forwarded-for", value.parse().unwrap());
+
+		headers
+	}
+
+	#[test]
+	fn forwarded_for_is_only_believed_from_trusted_proxies() {
+		let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+		let peer = Some(SocketAddr::new(proxy, 4000));
+		let headers = forwarded("6.6.6.6, 1.2.3.4, 10.0.0.2");
+
+		assert_eq!(client_ip(&headers, peer, &[]), Some(proxy));
+		assert_eq!(
+			client_ip(&headers, peer, &[proxy]),
+			Some("10.0.0.2".parse().unwrap())
+		);
+		assert_eq!(
+			client_ip(&headers, peer, &[proxy, "10.0.0.2".parse().unwrap()]),
+			Some("1.2.3.4".parse().unwrap())
+		);
+		assert_eq!(client_ip(&forwarded("junk"), peer, &[proxy]), Some(proxy));
+		assert_eq!(client_ip(&HeaderMap::new(), peer, &[proxy]), Some(proxy));
+	}
+}
@@ -0,0 +1,428 @@
+use std::str::FromStr;
+
+use axum::{
+	async_trait,
+	body::HttpBody,
+	extract::{rejection::JsonRejection, FromRequest},
+	http::{header, HeaderValue, Request},
+	response::{IntoResponse, Response},
+	BoxError, Json,
+};
+use serde::{
+	de::{
+		self,
+		value::{MapDeserializer, SeqDeserializer},
+		DeserializeOwned, IntoDeserializer, Visitor,
+	},
+	forward_to_deserialize_any, Serialize,
+};
+use serde_json::{Map, Value};
+
+use crate::{Error, State};
+
+// DTOs are declared snake_case; other casings are derived from that when rendering, and the field
+// names of request bodies are mapped back to it while parsing
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum Case {
+	#[default]
+	Snake,
+	Camel,
+}
+
+impl FromStr for Case {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"snake" | "snake_case" => Ok(Case::Snake),
+			"camel" | "camelCase" => Ok(Case::Camel),
+			_ => Err(format!("unknown case '{}', expected snake or camel", s)),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Format {
+	pub case: Case,
+	pub pretty: bool,
+}
+
+impl Format {
+	pub fn render<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, serde_json::Error> {
+		let mut value = serde_json::to_value(value)?;
+
+		if self.case == Case::Camel {
+			camelize(&mut value);
+		}
+
+		if self.pretty {
+			serde_json::to_vec_pretty(&value)
+		} else {
+			serde_json::to_vec(&value)
+		}
+	}
+
+	pub fn reply<T: Serialize>(&self, value: T) -> Reply<T> {
+		Reply(*self, value)
+	}
+}
+
+// a Json-like responder that honours the configured Format
+pub struct Reply<T>(Format, T);
+
+impl<T: Serialize> IntoResponse for Reply<T> {
+	fn into_response(self) -> Response {
+		match self.0.render(&self.1) {
+			Ok(body) => (
+				[(
+					header::CONTENT_TYPE,
+					HeaderValue::from_static("application/json"),
+				)],
+				body,
+			)
+				.into_response(),
+			Err(e) => {
+				tracing::error!(error = %e, "failed to render a response");
+
+				Error::Internal.into_response()
+			}
+		}
+	}
+}
+
+// a Json-like extractor that, in strict mode, rejects bodies carrying fields the target type doesn't know
+pub struct Body<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<State, B> for Body<T>
+where
+	T: DeserializeOwned,
+	B: HttpBody + Send + 'static,
+	B::Data: Send,
+	B::Error: Into<BoxError>,
+{
+	type Rejection = Response;
+
+	async fn from_request(req: Request<B>, state: &State) -> Result<Self, Self::Rejection> {
+		let Json(value) = Json::<Value>::from_request(req, state)
+			.await
+			.map_err(|rejection| match rejection {
+				JsonRejection::MissingJsonContentType(_) => {
+					Error::UnsupportedMediaType(rejection.body_text())
+				}
+				_ => Error::BadRequest(rejection.body_text()),
+			})
+			.map_err(IntoResponse::into_response)?;
+
+		let case = state.json.case;
+		let mut unknown = Vec::new();
+		let body = serde_ignored::deserialize(Cased(case, value), |path| {
+			unknown.push(unknown_path(case, &path.to_string()))
+		})
+		.map_err(|e| Error::InvalidBody(e.to_string()).into_response())?;
+
+		if state.strict && !unknown.is_empty() {
+			Err(Error::UnknownFields(unknown).into_response())
+		} else {
+			Ok(Body(body))
+		}
+	}
+}
+
+// the unknown field itself is reported as the client sent it, the fields leading to it in the
+// configured case
+fn unknown_path(case: Case, path: &str) -> String {
+	match (case, path.rsplit_once('.')) {
+		(Case::Camel, Some((parents, field))) => format!("{}.{}", path_to_camel(parents), field),
+		_ => path.to_string(),
+	}
+}
+
+// a json value that, with camel case, maps the keys of objects parsed into structs to the struct's
+// snake_case field names; the keys of free-form maps are client data and are left alone
+struct Cased(Case, Value);
+
+impl<'de> IntoDeserializer<'de, serde_json::Error> for Cased {
+	type Deserializer = Self;
+
+	fn into_deserializer(self) -> Self {
+		self
+	}
+}
+
+impl Cased {
+	fn visit_object<'de, V: Visitor<'de>>(
+		case: Case,
+		map: Map<String, Value>,
+		fields: &[&str],
+		visitor: V,
+	) -> Result<V::Value, serde_json::Error> {
+		let mut map = MapDeserializer::new(map.into_iter().map(|(key, value)| {
+			let key = match case {
+				Case::Camel if !fields.contains(&key.as_str()) => {
+					let snake = to_snake(&key);
+
+					if fields.contains(&snake.as_str()) {
+						snake
+					} else {
+						key
+					}
+				}
+				_ => key,
+			};
+
+			(key, Cased(case, value))
+		}));
+		let value = visitor.visit_map(&mut map)?;
+		map.end()?;
+
+		Ok(value)
+	}
+}
+
+impl<'de> de::Deserializer<'de> for Cased {
+	type Error = serde_json::Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+		match self.1 {
+			Value::Object(map) => Self::visit_object(self.0, map, &[], visitor),
+			Value::Array(items) => {
+				let case = self.0;
+				let mut items =
+					SeqDeserializer::new(items.into_iter().map(|item| Cased(case, item)));
+				let value = visitor.visit_seq(&mut items)?;
+				items.end()?;
+
+				Ok(value)
+			}
+			value => value.deserialize_any(visitor),
+		}
+	}
+
+	fn deserialize_struct<V: Visitor<'de>>(
+		self,
+		name: &'static str,
+		fields: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, Self::Error> {
+		match self.1 {
+			Value::Object(map) => Self::visit_object(self.0, map, fields, visitor),
+			value => value.deserialize_struct(name, fields, visitor),
+		}
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+		match self.1 {
+			Value::Null => visitor.visit_none(),
+			_ => visitor.visit_some(self),
+		}
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(
+		self,
+		_name: &'static str,
+		visitor: V,
+	) -> Result<V::Value, Self::Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_enum<V: Visitor<'de>>(
+		self,
+		name: &'static str,
+		variants: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, Self::Error> {
+		self.1.deserialize_enum(name, variants, visitor)
+	}
+
+	forward_to_deserialize_any! {
+		bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+		unit_struct seq tuple tuple_struct map identifier ignored_any
+	}
+}
+
+// renames every object key, so responses must not use data (ids, tokens) as object keys
+fn camelize(value: &mut Value) {
+	match value {
+		Value::Object(map) => {
+			*map = std::mem::take(map)
+				.into_iter()
+				.map(|(key, mut value)| {
+					camelize(&mut value);
+
+					(to_camel(&key), value)
+				})
+				.collect::<Map<_, _>>();
+		}
+		Value::Array(items) => items.iter_mut().for_each(camelize),
+		_ => {}
+	}
+}
+
+fn to_snake(key: &str) -> String {
+	let mut out = String::with_capacity(key.len() + 4);
+
+	for c in key.chars() {
+		if c.is_uppercase() {
+			if !out.is_empty() {
+				out.push('_');
+			}
+
+			out.extend(c.to_lowercase());
+		} else {
+			out.push(c);
+		}
+	}
+
+	out
+}
+
+// camelizes the field names in a path such as `events[0].properties["screen_name"]`, leaving
+// indices and the quoted map keys alone
+pub fn path_to_camel(path: &str) -> String {
+	let mut out = String::with_capacity(path.len());
+	let mut upper = false;
+	let mut quoted = false;
+	let mut escaped = false;
+
+	for c in path.chars() {
+		if quoted {
+			quoted = escaped || c != '"';
+			escaped = !escaped && c == '\\';
+			out.push(c);
+		} else if c == '"' {
+			quoted = true;
+			out.push(c);
+		} else if c == '_' {
+			upper = !out.is_empty() && !out.ends_with(['.', ']']);
+		} else if upper {
+			out.extend(c.to_uppercase());
+			upper = false;
+		} else {
+			out.push(c);
+		}
+	}
+
+	out
+}
+
+fn to_camel(key: &str) -> String {
+	let mut out = String::with_capacity(key.len());
+	let mut upper = false;
+
+	for c in key.chars() {
+		if c == '_' {
+			upper = !out.is_empty();
+		} else if upper {
+			out.extend(c.to_uppercase());
+			upper = false;
+		} else {
+			out.push(c);
+		}
+	}
+
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::Deserialize;
+	use serde_json::json;
+
+	use super::*;
+
+	#[test]
+	fn keys_round_trip_between_cases() {
+		assert_eq!(to_camel("max_uses"), "maxUses");
+		assert_eq!(to_snake("maxUses"), "max_uses");
+		assert_eq!(to_snake("max_uses"), "max_uses");
+		assert_eq!(to_snake("ttl"), "ttl");
+	}
+
+	#[derive(Deserialize, Debug)]
+	struct Event {
+		starts_at: u64,
+		properties: Map<String, Value>,
+	}
+
+	#[derive(Deserialize, Debug)]
+	struct Request {
+		max_uses: Option<u64>,
+		events: Vec<Event>,
+	}
+
+	#[test]
+	fn only_field_names_are_mapped_to_snake_case() {
+		let value = json!({
+			"maxUses": 1,
+			"events": [{"startsAt": 2, "properties": {"screenName": "home", "user_id": 3}}],
+		});
+		let req = Request::deserialize(Cased(Case::Camel, value)).unwrap();
+
+		assert_eq!(req.max_uses, Some(1));
+		assert_eq!(req.events[0].starts_at, 2);
+		assert_eq!(
+			Value::Object(req.events[0].properties.clone()),
+			json!({"screenName": "home", "user_id": 3})
+		);
+
+		// snake case bodies are left as they are either way
+		let value = json!({"max_uses": 1, "events": []});
+		assert_eq!(
+			Request::deserialize(Cased(Case::Snake, value))
+				.unwrap()
+				.max_uses,
+			Some(1)
+		);
+	}
+
+	#[test]
+	fn paths_are_camelized_up_to_data() {
+		assert_eq!(
+			path_to_camel(r#"events[0].starts_at.properties["user_id"]"#),
+			r#"events[0].startsAt.properties["user_id"]"#
+		);
+		assert_eq!(
+			path_to_camel(r#"properties["a\"_b"].max_uses"#),
+			r#"properties["a\"_b"].maxUses"#
+		);
+		assert_eq!(
+			unknown_path(Case::Camel, "events.0.extra_field"),
+			"events.0.extra_field"
+		);
+		assert_eq!(unknown_path(Case::Camel, "max_uses.x"), "maxUses.x");
+	}
+
+	async fn extract(state: &State, body: &'static str) -> Result<Request, Response> {
+		let req = axum::http::Request::post("/")
+			.header(header::CONTENT_TYPE, "application/json")
+			.body(axum::body::Body::from(body))
+			.unwrap();
+
+		Body::<Request>::from_request(req, state)
+			.await
+			.map(|Body(body)| body)
+	}
+
+	#[tokio::test]
+	async fn strict_bodies_reject_unknown_fields() {
+		let body = r#"{"max_uses": 1, "events": [], "extra": true}"#;
+
+		let res = extract(&State::new().with_strict_bodies(true), body)
+			.await
+			.unwrap_err();
+		assert_eq!(res.status(), axum::http::StatusCode::BAD_REQUEST);
+		let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+		let body: Value = serde_json::from_slice(&body).unwrap();
+		assert_eq!(body["code"], "unknown_fields");
+		assert_eq!(body["details"]["fields"], json!(["extra"]));
+
+		let req = extract(
+			&State::new(),
+			r#"{"max_uses": 1, "events": [], "extra": true}"#,
+		)
+		.await
+		.unwrap();
+		assert_eq!(req.max_uses, Some(1));
+	}
+}
@@ -0,0 +1,496 @@
+use json::{Body, Reply};
+use lock::Lock;
+use params::Path;
+use std::{net::IpAddr, sync::Arc, time::Duration};
+use store::{LockStore, MemoryStore};
+use validate::Validate;
+
+pub use error::Error;
+
+use axum::{
+	extract,
+	http::{Request, StatusCode},
+	middleware,
+	routing::{get, post},
+	Router,
+};
+
+use dashmap::DashMap;
+use metrics_exporter_prometheus::PrometheusHandle;
+use tower_http::{
+	trace::{DefaultOnResponse, TraceLayer},
+	LatencyUnit,
+};
+use tracing::Level;
+
+pub mod admin;
+pub mod analytics;
+pub mod announcements;
+pub mod auth;
+pub mod client_version;
+pub mod config;
+pub mod debug;
+pub mod dto;
+pub mod error;
+pub mod events;
+pub mod expiry;
+pub mod health;
+pub mod honeypot;
+pub mod json;
+pub mod lock;
+pub mod lock_state;
+pub mod logging;
+pub mod openapi;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod params;
+pub mod prometheus;
+pub mod quota;
+pub mod reload;
+pub mod runtime;
+pub mod shutdown;
+pub mod slo;
+pub mod snapshot;
+pub mod status;
+pub mod store;
+pub mod systemd;
+pub mod timing;
+pub mod validate;
+pub mod version;
+
+#[derive(Clone)]
+pub struct State {
+	pub(crate) store: Arc<dyn LockStore>,
+	pub(crate) json: json::Format,
+	pub(crate) strict: bool,
+	pub(crate) slo: Arc<slo::Tracker>,
+	pub(crate) admin_token: Option<String>,
+	pub(crate) slow_request: Option<Duration>,
+	pub(crate) log_filter: Option<Arc<logging::LogFilter>>,
+	pub(crate) honeypot: Arc<honeypot::Hits>,
+	pub(crate) lock_states: Arc<lock_state::Cache>,
+	pub(crate) default_ttl: Option<Duration>,
+	pub(crate) api_tokens: Arc<[String]>,
+	pub(crate) private_reads: bool,
+	pub(crate) occupancy: Arc<analytics::Occupancy>,
+	pub(crate) status: Arc<status::Board>,
+	pub(crate) announcements: Arc<announcements::Announcements>,
+	pub(crate) client_policy: Option<client_version::Policy>,
+	pub(crate) events: Arc<dyn events::EventSink>,
+	pub(crate) event_sample_rate: f64,
+	pub(crate) metrics: Option<PrometheusHandle>,
+	pub(crate) trusted_proxies: Arc<[IpAddr]>,
+	pub(crate) gate: Option<Arc<health::Gate>>,
+}
+
+impl Default for State {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl State {
+	pub fn new() -> Self {
+		Self::new_with_data(Arc::new(DashMap::new()))
+	}
+
+	pub fn new_with_data(data: Arc<DashMap<String, Lock>>) -> Self {
+		Self::new_with_store(Arc::new(MemoryStore::new(data)))
+	}
+
+	pub fn new_with_store(store: Arc<dyn LockStore>) -> Self {
+		Self {
+			store,
+			json: json::Format::default(),
+			strict: false,
+			slo: Arc::new(slo::Tracker::default()),
+			admin_token: None,
+			slow_request: None,
+			log_filter: None,
+			honeypot: Arc::new(honeypot::Hits::default()),
+			lock_states: Arc::new(lock_state::Cache::default()),
+			default_ttl: None,
+			api_tokens: Arc::new([]),
+			private_reads: false,
+			occupancy: Arc::new(analytics::Occupancy::default()),
+			status: Arc::new(status::Board::default()),
+			announcements: Arc::new(announcements::Announcements::default()),
+			client_policy: None,
+			events: Arc::new(events::LogSink),
+			event_sample_rate: 1.0,
+			metrics: None,
+			trusted_proxies: Arc::new([]),
+			gate: None,
+		}
+	}
+
+	pub fn with_json_format(mut self, json: json::Format) -> Self {
+		self.json = json;
+
+		self
+	}
+
+	pub fn with_strict_bodies(mut self, strict: bool) -> Self {
+		self.strict = strict;
+
+		self
+	}
+
+	pub fn with_slos(mut self, objectives: Vec<slo::Objective>) -> Self {
+		self.slo = Arc::new(slo::Tracker::new(objectives));
+
+		self
+	}
+
+	pub fn with_admin_token(mut self, token: Option<String>) -> Self {
+		self.admin_token = token;
+
+		self
+	}
+
+	pub fn with_log_filter(mut self, filter: logging::LogFilter) -> Self {
+		self.log_filter = Some(Arc::new(filter));
+
+		self
+	}
+
+	pub fn with_slow_request_threshold(mut self, threshold: Option<Duration>) -> Self {
+		self.slow_request = threshold;
+
+		self
+	}
+
+	pub fn with_default_lock_ttl(mut self, ttl: Option<Duration>) -> Self {
+		self.default_ttl = ttl;
+
+		self
+	}
+
+	// lock routes are open to anyone while no token is configured
+	pub fn with_api_tokens(mut self, tokens: Vec<String>) -> Self {
+		self.api_tokens = tokens.into();
+
+		self
+	}
+
+	pub fn with_private_reads(mut self, private: bool) -> Self {
+		self.private_reads = private;
+
+		self
+	}
+
+	// X-Forwarded-For is ignored unless the request comes through one of these
+	pub fn with_trusted_proxies(mut self, proxies: Vec<IpAddr>) -> Self {
+		self.trusted_proxies = proxies.into();
+
+		self
+	}
+
+	pub fn with_client_policy(mut self, policy: Option<client_version::Policy>) -> Self {
+		self.client_policy = policy;
+
+		self
+	}
+
+	pub fn with_event_sink(mut self, sink: Arc<dyn events::EventSink>) -> Self {
+		self.events = sink;
+
+		self
+	}
+
+	// the share of tracked events that is kept, between 0 and 1
+	pub fn with_event_sample_rate(mut self, rate: f64) -> Self {
+		self.event_sample_rate = rate;
+
+		self
+	}
+
+	pub fn with_metrics(mut self, handle: PrometheusHandle) -> Self {
+		self.metrics = Some(handle);
+
+		self
+	}
+
+	// requests are refused with 503 until the gate is opened
+	pub fn with_startup_gate(mut self, gate: Arc<health::Gate>) -> Self {
+		self.gate = Some(gate);
+
+		self
+	}
+
+	pub fn storage(&self) -> &'static str {
+		self.store.name()
+	}
+}
+
+pub fn router(state: State) -> Router {
+	let mut writes = Router::new()
+		.route("/lock/:id", post(lock))
+		.route("/unlock/:id", post(unlock))
+		.route("/locks", post(issue))
+		.route("/locks/verify", post(verify))
+		.route("/locks/:id/relock", post(relock))
+		.route("/purge", post(purge));
+	let mut reads = Router::new()
+		.route("/locks/:id/state", get(lock_state::get))
+		.route("/locks/:id/analytics", get(analytics::occupancy))
+		.route("/usage", get(usage));
+
+	if !state.api_tokens.is_empty() {
+		let auth = middleware::from_fn_with_state(state.clone(), auth::require_api_token);
+
+		writes = writes.route_layer(auth.clone());
+
+		if state.private_reads {
+			reads = reads.route_layer(auth);
+		}
+	}
+
+	let mut api = writes
+		.merge(reads)
+		.route("/events/track", post(events::track));
+
+	if state.client_policy.is_some() {
+		api = api.route_layer(middleware::from_fn_with_state(
+			state.clone(),
+			client_version::require,
+		));
+	}
+
+	let mut router = Router::new()
+		.merge(api)
+		.route("/version", get(version::version))
+		.route("/status", get(status::status))
+		.route("/healthz", get(health::healthz))
+		.route("/readyz", get(health::readyz))
+		.route("/announcements", get(announcements::active))
+		.route("/openapi.json", get(openapi::spec))
+		.route("/docs", get(openapi::docs))
+		.merge(honeypot::router());
+
+	if state.metrics.is_some() {
+		router = router.route("/metrics", get(prometheus::render));
+	}
+
+	if state.admin_token.is_some() {
+		router = router
+			.nest("/admin", admin::router(state.clone()))
+			.nest("/debug", debug::router(state.clone()));
+	}
+
+	router = router
+		.fallback(error::fallback)
+		.route_layer(middleware::from_fn(timing::handler))
+		.route_layer(middleware::from_fn_with_state(state.clone(), slo::track))
+		.route_layer(middleware::from_fn(prometheus::track))
+		.layer(middleware::from_fn_with_state(
+			state.clone(),
+			timing::observe,
+		))
+		.layer(middleware::from_fn_with_state(
+			state.clone(),
+			announcements::header,
+		));
+
+	// outside of the metrics, so that refusals while starting up do not count against any slo
+	if let Some(gate) = &state.gate {
+		router = router.layer(middleware::from_fn_with_state(gate.clone(), health::gate));
+	}
+
+	router
+		.layer(middleware::from_fn_with_state(state.json, error::negotiate))
+		.layer(
+			TraceLayer::new_for_http()
+				.make_span_with(|req: &Request<_>| {
+					let span = tracing::info_span!(
+						"request",
+						method = %req.method(),
+						path = req.uri().path()
+					);
+					#[cfg(feature = "otlp")]
+					otlp::set_parent(&span, req.headers());
+
+					span
+				})
+				.on_response(
+					DefaultOnResponse::new()
+						.level(Level::INFO)
+						.latency_unit(LatencyUnit::Micros),
+				),
+		)
+		.with_state(state)
+}
+
+#[utoipa::path(
+	post,
+	path = "/lock/{id}",
+	tag = "locks",
+	params(("id" = String, Path, description = "lock id")),
+	request_body = dto::v1::LockRequest,
+	responses(
+		(status = 201, description = "the lock was stored"),
+		(status = 422, description = "the request failed validation", body = error::ErrorBody),
+		(status = 507, description = "the storage limit was reached", body = error::ErrorBody),
+	),
+	security((), ("api_token" = [])),
+)]
+pub async fn lock(
+	extract::State(state): extract::State<State>,
+	Path(id): Path<String>,
+	Body(req): Body<dto::v1::LockRequest>,
+) -> Result<StatusCode, Error> {
+	let mut errors = validate::Errors::default();
+	validate::id(&mut errors, "id", &id);
+	req.validate(&mut errors);
+	errors.into_result()?;
+
+	let ttl = req.ttl.map(Duration::from_secs).or(state.default_ttl);
+	let lock = Lock::new(req.token, ttl).with_max_uses(req.max_uses);
+
+	timing::storage("insert", state.store.insert(id.clone(), lock)).await?;
+	state.lock_states.record(&id, true);
+
+	Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+	post,
+	path = "/locks",
+	tag = "locks",
+	responses(
+		(status = 201, description = "a lock with a generated id and token", body = dto::v1::IssueResponse),
+		(status = 507, description = "the storage limit was reached", body = error::ErrorBody),
+	),
+	security((), ("api_token" = [])),
+)]
+pub async fn issue(
+	extract::State(state): extract::State<State>,
+) -> Result<(StatusCode, Reply<dto::v1::IssueResponse>), Error> {
+	let id = lock::issue_id();
+	let lock = Lock::new(lock::issue_token(), state.default_ttl);
+	let res = dto::v1::IssueResponse {
+		id: id.clone(),
+		token: lock.token.clone(),
+		expires_at: lock.expires_at,
+	};
+
+	timing::storage("insert", state.store.insert(id.clone(), lock)).await?;
+	state.lock_states.record(&id, true);
+
+	Ok((StatusCode::CREATED, state.json.reply(res)))
+}
+
+// an unknown id is reported the same way as a wrong token; every success spends one use
+#[utoipa::path(
+	post,
+	path = "/locks/verify",
+	tag = "locks",
+	request_body = dto::v1::VerifyRequest,
+	responses(
+		(status = 200, description = "whether the token matched", body = dto::v1::VerifyResponse),
+		(status = 422, description = "the request failed validation", body = error::ErrorBody),
+	),
+	security((), ("api_token" = [])),
+)]
+pub async fn verify(
+	extract::State(state): extract::State<State>,
+	Body(req): Body<dto::v1::VerifyRequest>,
+) -> Result<Reply<dto::v1::VerifyResponse>, Error> {
+	let mut errors = validate::Errors::default();
+	req.validate(&mut errors);
+	errors.into_result()?;
+
+	let lock = timing::storage("consume", state.store.consume(&req.id, &req.token)).await?;
+	let uses_left = lock.as_ref().and_then(|lock| lock.uses_left);
+
+	if uses_left == Some(0) {
+		state.lock_states.record(&req.id, false);
+	}
+
+	Ok(state.json.reply(dto::v1::VerifyResponse {
+		valid: lock.is_some(),
+		uses_left,
+	}))
+}
+
+#[utoipa::path(
+	post,
+	path = "/unlock/{id}",
+	tag = "locks",
+	params(("id" = String, Path, description = "lock id")),
+	responses(
+		(status = 200, description = "the lock was removed; its token is handed back", body = dto::v1::LockResponse),
+		(status = 410, description = "no such lock", body = error::ErrorBody),
+	),
+	security((), ("api_token" = [])),
+)]
+pub async fn unlock(
+	extract::State(state): extract::State<State>,
+	Path(id): Path<String>,
+) -> Result<(StatusCode, Reply<dto::v1::LockResponse>), Error> {
+	if let Some(lock) = timing::storage("remove", state.store.remove(&id)).await? {
+		state.lock_states.record(&id, false);
+		state.occupancy.record(&id);
+
+		Ok((StatusCode::OK, state.json.reply(lock.into())))
+	} else {
+		Err(Error::NotFound)
+	}
+}
+
+// invalidates a lock before its lifetime ends without handing its token back
+#[utoipa::path(
+	post,
+	path = "/locks/{id}/relock",
+	tag = "locks",
+	params(("id" = String, Path, description = "lock id")),
+	responses(
+		(status = 204, description = "the lock was invalidated"),
+		(status = 410, description = "no such lock", body = error::ErrorBody),
+	),
+	security((), ("api_token" = [])),
+)]
+pub async fn relock(
+	extract::State(state): extract::State<State>,
+	Path(id): Path<String>,
+) -> Result<StatusCode, Error> {
+	if timing::storage("remove", state.store.remove(&id))
+		.await?
+		.is_some()
+	{
+		state.lock_states.record(&id, false);
+
+		Ok(StatusCode::NO_CONTENT)
+	} else {
+		Err(Error::NotFound)
+	}
+}
+
+#[utoipa::path(
+	post,
+	path = "/purge",
+	tag = "locks",
+	responses((status = 200, description = "every lock was removed")),
+	security((), ("api_token" = [])),
+)]
+pub async fn purge(extract::State(state): extract::State<State>) -> Result<StatusCode, Error> {
+	timing::storage("clear", state.store.clear()).await?;
+	state.lock_states.unlock_all();
+
+	Ok(StatusCode::OK)
+}
+
+#[utoipa::path(
+	get,
+	path = "/usage",
+	tag = "locks",
+	responses((status = 200, description = "storage usage against the configured limits", body = quota::Report)),
+	security((), ("api_token" = [])),
+)]
+pub async fn usage(
+	extract::State(state): extract::State<State>,
+) -> Result<Reply<quota::Report>, Error> {
+	Ok(state
+		.json
+		.reply(timing::storage("usage", state.store.usage()).await?))
+}
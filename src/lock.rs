@@ -1,7 +1,70 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::{distributions::Alphanumeric, Rng};
 use serde::{self, Deserialize, Serialize};
 
+const ID_LEN: usize = 16;
+const TOKEN_LEN: usize = 32;
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 #[serde(crate = "self::serde")]
 pub struct Lock {
 	pub token: String,
+	// unix seconds after which the lock no longer counts; never when unset
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub expires_at: Option<u64>,
+	// successful verifications left before the lock goes away; unlimited when unset
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub uses_left: Option<u32>,
+}
+
+pub fn now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default()
+}
+
+impl Lock {
+	pub fn new(token: String, ttl: Option<Duration>) -> Self {
+		Self {
+			token,
+			expires_at: ttl.map(|ttl| now().saturating_add(ttl.as_secs())),
+			uses_left: None,
+		}
+	}
+
+	pub fn with_max_uses(mut self, uses: Option<u32>) -> Self {
+		self.uses_left = uses;
+
+		self
+	}
+
+	pub fn is_expired(&self, now: u64) -> bool {
+		self.expires_at.is_some_and(|at| at <= now)
+	}
+
+	pub fn verify(&self, token: &str) -> bool {
+		constant_time_eq(self.token.as_bytes(), token.as_bytes())
+	}
+}
+
+fn random(len: usize) -> String {
+	rand::thread_rng()
+		.sample_iter(&Alphanumeric)
+		.take(len)
+		.map(char::from)
+		.collect()
+}
+
+pub fn issue_id() -> String {
+	random(ID_LEN)
+}
+
+pub fn issue_token() -> String {
+	random(TOKEN_LEN)
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
 }
@@ -0,0 +1,302 @@
+use std::{
+	hash::{BuildHasher, RandomState},
+	ops::{Deref, DerefMut},
+	sync::atomic::{AtomicU64, Ordering},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+	extract,
+	http::{header, HeaderMap, HeaderValue, StatusCode},
+	response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use serde::{self, Deserialize, Serialize};
+use tokio::sync::watch;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+	params::{Path, Query},
+	store::{LockStore, StoreError},
+	timing, validate, Error, State,
+};
+
+const MAX_WAIT_SECS: u64 = 60;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct LockState {
+	pub locked: bool,
+	pub version: u64,
+}
+
+// the state of an id without a lock, until it gets one while being polled
+const MISSING: LockState = LockState {
+	locked: false,
+	version: 0,
+};
+
+// what this process last saw of the locks someone is polling right now, locked or not; entries go
+// away with their last poller. changes made by other instances sharing a database are not picked up
+// meanwhile
+#[derive(Debug)]
+pub struct Cache {
+	states: DashMap<String, watch::Sender<LockState>>,
+	next_version: AtomicU64,
+	// keys the versions of locks loaded from the store, which must not reveal their tokens
+	hasher: RandomState,
+}
+
+impl Default for Cache {
+	fn default() -> Self {
+		// seeded from the clock so that versions keep increasing across restarts
+		let seed = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_micros() as u64)
+			.unwrap_or_default();
+
+		Self {
+			states: DashMap::new(),
+			next_version: AtomicU64::new(seed),
+			hasher: RandomState::new(),
+		}
+	}
+}
+
+impl Cache {
+	fn next_version(&self) -> u64 {
+		self.next_version.fetch_add(1, Ordering::Relaxed)
+	}
+
+	// only locks that are already being polled are tracked
+	pub fn record(&self, id: &str, locked: bool) {
+		if let Some(state) = self.states.get(id) {
+			state.send_replace(LockState {
+				locked,
+				version: self.next_version(),
+			});
+		}
+	}
+
+	pub fn unlock_all(&self) {
+		for state in self.states.iter() {
+			state.send_replace(LockState {
+				locked: false,
+				version: self.next_version(),
+			});
+		}
+	}
+
+	pub async fn subscribe<'a>(
+		&'a self,
+		store: &dyn LockStore,
+		id: &'a str,
+	) -> Result<Subscription<'a>, StoreError> {
+		// registered before the store is read, so that changes made meanwhile are not missed
+		let subscription = Subscription {
+			cache: self,
+			id,
+			receiver: Some(
+				self.states
+					.entry(id.to_string())
+					.or_insert_with(|| watch::channel(MISSING).0)
+					.subscribe(),
+			),
+		};
+		let seen = *subscription.borrow();
+		// derived from the lock rather than counted, so that polling it again after its entry went
+		// away does not look like a change
+		let loaded = match timing::storage("get", store.get(id)).await? {
+			Some(lock) => LockState {
+				locked: true,
+				version: self.hasher.hash_one((id, &lock.token)),
+			},
+			None => MISSING,
+		};
+
+		// whatever was recorded while the store was read is newer than what it returned
+		if let Some(state) = self.states.get(id) {
+			state.send_if_modified(|state| {
+				let stale = *state == seen && state.locked != loaded.locked;
+
+				if stale {
+					*state = loaded;
+				}
+
+				stale
+			});
+		}
+
+		Ok(subscription)
+	}
+}
+
+// a poller's view of one lock; the cache forgets the lock once its last poller is gone
+pub struct Subscription<'a> {
+	cache: &'a Cache,
+	id: &'a str,
+	receiver: Option<watch::Receiver<LockState>>,
+}
+
+impl Deref for Subscription<'_> {
+	type Target = watch::Receiver<LockState>;
+
+	fn deref(&self) -> &Self::Target {
+		self.receiver.as_ref().unwrap()
+	}
+}
+
+impl DerefMut for Subscription<'_> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.receiver.as_mut().unwrap()
+	}
+}
+
+impl Drop for Subscription<'_> {
+	fn drop(&mut self) {
+		self.receiver.take();
+		self.cache
+			.states
+			.remove_if(self.id, |_, state| state.receiver_count() == 0);
+	}
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[serde(crate = "self::serde")]
+#[into_params(parameter_in = Query)]
+pub struct PollParams {
+	// seconds to hold the request open while the state still matches If-None-Match
+	pub wait: Option<u64>,
+}
+
+fn etag(version: u64) -> HeaderValue {
+	HeaderValue::from_str(&format!("\"{}\"", version)).unwrap()
+}
+
+fn matches(headers: &HeaderMap, etag: &HeaderValue) -> bool {
+	let Some(value) = headers
+		.get(header::IF_NONE_MATCH)
+		.and_then(|value| value.to_str().ok())
+	else {
+		return false;
+	};
+	let etag = etag.to_str().unwrap_or_default();
+
+	value
+		.split(',')
+		.map(str::trim)
+		.any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+#[utoipa::path(
+	get,
+	path = "/locks/{id}/state",
+	operation_id = "get_lock_state",
+	tag = "locks",
+	params(
+		("id" = String, Path, description = "lock id"),
+		("If-None-Match" = Option<String>, Header, description = "an etag from an earlier response"),
+		PollParams,
+	),
+	responses(
+		(status = 200, description = "the current state", body = LockState),
+		(status = 304, description = "nothing changed while waiting"),
+		(status = 422, description = "the id is malformed", body = crate::error::ErrorBody),
+	),
+	security((), ("api_token" = [])),
+)]
+pub async fn get(
+	extract::State(state): extract::State<State>,
+	Path(id): Path<String>,
+	Query(params): Query<PollParams>,
+	headers: HeaderMap,
+) -> Result<Response, Error> {
+	let mut errors = validate::Errors::default();
+	validate::id(&mut errors, "id", &id);
+	errors.into_result()?;
+
+	let mut states = state.lock_states.subscribe(&*state.store, &id).await?;
+	let current = *states.borrow_and_update();
+
+	if matches(&headers, &etag(current.version)) {
+		let wait = Duration::from_secs(params.wait.unwrap_or_default().min(MAX_WAIT_SECS));
+
+		if !matches!(
+			tokio::time::timeout(wait, states.changed()).await,
+			Ok(Ok(()))
+		) {
+			return Ok((
+				StatusCode::NOT_MODIFIED,
+				[(header::ETAG, etag(current.version))],
+			)
+				.into_response());
+		}
+	}
+
+	let current = *states.borrow();
+
+	Ok((
+		[(header::ETAG, etag(current.version))],
+		state.json.reply(current),
+	)
+		.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{lock::Lock, store::MemoryStore};
+
+	#[tokio::test]
+	async fn only_polled_locks_are_cached() {
+		let cache = Cache::default();
+		let store = MemoryStore::new(Default::default());
+		store
+			.insert("door".to_string(), Lock::new("tok".to_string(), None))
+			.await
+			.unwrap();
+
+		let missing = cache.subscribe(&store, "nope").await.unwrap();
+		assert_eq!(*missing.borrow(), MISSING);
+		assert_eq!(cache.states.len(), 1);
+		drop(missing);
+		assert!(cache.states.is_empty());
+
+		let first = cache.subscribe(&store, "door").await.unwrap();
+		let second = cache.subscribe(&store, "door").await.unwrap();
+		let version = first.borrow().version;
+		assert!(first.borrow().locked);
+		assert_eq!(cache.states.len(), 1);
+
+		drop(first);
+		assert_eq!(cache.states.len(), 1);
+		drop(second);
+		assert!(cache.states.is_empty());
+
+		// polling again after the entry went away sees the same version
+		let again = cache.subscribe(&store, "door").await.unwrap();
+		assert_eq!(again.borrow().version, version);
+	}
+
+	#[tokio::test]
+	async fn pollers_of_a_missing_lock_see_it_locked() {
+		let cache = Cache::default();
+		let store = MemoryStore::new(Default::default());
+
+		let mut poller = cache.subscribe(&store, "door").await.unwrap();
+		assert!(!poller.borrow_and_update().locked);
+
+		cache.record("door", true);
+		poller.changed().await.unwrap();
+		assert!(poller.borrow().locked);
+
+		// a later poller does not take back what was recorded
+		store
+			.insert("door".to_string(), Lock::new("tok".to_string(), None))
+			.await
+			.unwrap();
+		let version = poller.borrow().version;
+		let other = cache.subscribe(&store, "door").await.unwrap();
+		assert_eq!(other.borrow().version, version);
+	}
+}
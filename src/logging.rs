@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, env};
+
+use axum::extract;
+use serde::{self, Deserialize, Serialize};
+use tracing_subscriber::{
+	layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+use utoipa::ToSchema;
+
+use crate::{
+	json::{Body, Reply},
+	Error, State,
+};
+
+const DEFAULT_FILTER: &str = "info";
+// "json" for one object per line, for log aggregation; anything else is the human-readable format
+const FORMAT_ENV: &str = "TOUCHID_LOG_FORMAT";
+
+// swaps the filter of the log output at runtime; other layers (e.g. tokio-console) are unaffected
+#[derive(Debug)]
+pub struct LogFilter {
+	handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogFilter {
+	pub fn current(&self) -> String {
+		self.handle
+			.with_current(|filter| filter.to_string())
+			.unwrap_or_default()
+	}
+
+	pub fn set(&self, directives: &str) -> Result<(), String> {
+		let filter = EnvFilter::try_new(directives).map_err(|e| e.to_string())?;
+
+		self.handle.reload(filter).map_err(|e| e.to_string())
+	}
+}
+
+// installs the global subscriber, starting from RUST_LOG
+pub fn init() -> LogFilter {
+	let filter =
+		EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+	let (filter, handle) = reload::Layer::new(filter);
+	let output = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+	let json = env::var(FORMAT_ENV).is_ok_and(|format| format == "json");
+	let output = if json {
+		output.json().boxed()
+	} else {
+		output.boxed()
+	};
+	let registry = tracing_subscriber::registry().with(output.with_filter(filter));
+	// needs RUSTFLAGS="--cfg tokio_unstable"; serves tokio-console on 127.0.0.1:6669
+	#[cfg(feature = "console")]
+	let registry = registry.with(console_subscriber::spawn());
+	// exports spans when OTEL_EXPORTER_OTLP_ENDPOINT is set
+	#[cfg(feature = "otlp")]
+	let (registry, otlp) = match crate::otlp::layer() {
+		Ok(layer) => (registry.with(layer), None),
+		Err(e) => (registry.with(None), Some(e)),
+	};
+
+	registry.init();
+
+	#[cfg(feature = "otlp")]
+	if let Some(e) = otlp {
+		tracing::error!(error = %e, "otlp export disabled");
+	}
+
+	LogFilter { handle }
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct LogLevelRequest {
+	pub level: String,
+	#[serde(default)]
+	pub targets: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct LogLevelResponse {
+	pub filter: String,
+}
+
+#[utoipa::path(
+	get,
+	path = "/admin/log-level",
+	tag = "admin",
+	responses(
+		(status = 200, description = "the active log filter", body = LogLevelResponse),
+		(status = 410, description = "the filter cannot be changed at runtime", body = crate::error::ErrorBody),
+	),
+	security(("admin_token" = [])),
+)]
+pub async fn get_level(
+	extract::State(state): extract::State<State>,
+) -> Result<Reply<LogLevelResponse>, Error> {
+	let filter = state.log_filter.as_ref().ok_or(Error::NotFound)?;
+
+	Ok(state.json.reply(LogLevelResponse {
+		filter: filter.current(),
+	}))
+}
+
+#[utoipa::path(
+	put,
+	path = "/admin/log-level",
+	tag = "admin",
+	request_body = LogLevelRequest,
+	responses(
+		(status = 200, description = "the new log filter", body = LogLevelResponse),
+		(status = 400, description = "the directives do not parse", body = crate::error::ErrorBody),
+	),
+	security(("admin_token" = [])),
+)]
+pub async fn set_level(
+	extract::State(state): extract::State<State>,
+	Body(req): Body<LogLevelRequest>,
+) -> Result<Reply<LogLevelResponse>, Error> {
+	let filter = state.log_filter.as_ref().ok_or(Error::NotFound)?;
+	let directives = std::iter::once(req.level)
+		.chain(
+			req.targets
+				.into_iter()
+				.map(|(target, level)| format!("{}={}", target, level)),
+		)
+		.collect::<Vec<_>>()
+		.join(",");
+
+	filter.set(&directives).map_err(Error::BadRequest)?;
+	tracing::info!(filter = %directives, "log filter changed");
+
+	Ok(state.json.reply(LogLevelResponse {
+		filter: filter.current(),
+	}))
+}
@@ -1,92 +1,473 @@
-use lock::Lock;
-use std::sync::Arc;
-
-use axum::{
-	extract::{self, Path},
-	http::StatusCode,
-	response::IntoResponse,
-	routing::post,
-	Json, Router,
+use std::{
+	env, fs, io,
+	net::{IpAddr, SocketAddr},
+	path::PathBuf,
+	process::ExitCode,
+	sync::Arc,
+	time::Duration,
 };
 
-use dashmap::DashMap;
+use clap::{
+	builder::NonEmptyStringValueParser, Args, CommandFactory, FromArgMatches, Parser, Subcommand,
+};
+use touchid::{
+	client_version, config, expiry, health, json, lock, logging, prometheus,
+	quota::Limits,
+	reload, router, shutdown, slo,
+	snapshot::{self, Snapshot},
+	store::{DeferredStore, LockStore, MemoryStore},
+	systemd, validate, version, State,
+};
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
-mod lock;
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+	#[command(subcommand)]
+	command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+	/// Run the HTTP server; accepts a systemd-activated socket when one is passed in
+	Serve(Box<ServeArgs>),
+	/// Rewrite a data file in the current snapshot format
+	Migrate {
+		#[arg(long, env = "TOUCHID_DATA")]
+		data: PathBuf,
+	},
+	/// Create or check backups
+	Backup {
+		#[command(subcommand)]
+		command: BackupCommand,
+	},
+	/// Replace a data file with the contents of a backup; run while the server is stopped
+	Restore {
+		#[arg(long, env = "TOUCHID_DATA")]
+		data: PathBuf,
+		from: PathBuf,
+	},
+	/// Manage lock tokens
+	Token {
+		#[command(subcommand)]
+		command: TokenCommand,
+	},
+}
 
-#[derive(Clone)]
-pub struct State {
-	pub(crate) locks: Arc<DashMap<String, Lock>>,
+#[derive(Args)]
+struct ServeArgs {
+	/// Read options from this TOML file, keyed by their long names (e.g. `port = 3000`, `api-token = ["..."]`);
+	/// the command line and environment take precedence over it
+	#[arg(long, env = "TOUCHID_CONFIG")]
+	config: Option<PathBuf>,
+	/// Address to listen on; ignored when systemd passes in a socket
+	#[arg(long, env = "TOUCHID_BIND", default_value = "0.0.0.0")]
+	bind: IpAddr,
+	#[arg(long, env = "TOUCHID_PORT", default_value_t = 3000)]
+	port: u16,
+	/// Log filter such as "info" or "warn,touchid=debug"; overrides RUST_LOG
+	#[arg(long, env = "TOUCHID_LOG_LEVEL")]
+	log_level: Option<String>,
+	/// Persist locks to this file; state is kept in memory only when omitted
+	#[arg(long, env = "TOUCHID_DATA")]
+	data: Option<PathBuf>,
+	/// Store locks in a database instead of memory, e.g. "sqlite://touchid.db", "postgres://host/touchid" or "redis://host"
+	#[arg(long, env = "TOUCHID_DATABASE_URL", hide_env_values = true, conflicts_with_all = ["data", "max_locks", "max_bytes"])]
+	database_url: Option<String>,
+	/// Size of the database connection pool
+	#[arg(long, env = "TOUCHID_DATABASE_MAX_CONNECTIONS", default_value_t = 10)]
+	database_max_connections: u32,
+	/// Prefix of the keys locks are stored under in Redis; /purge removes every key starting with it
+	#[arg(
+		long,
+		env = "TOUCHID_REDIS_KEY_PREFIX",
+		default_value = "touchid:lock:",
+		value_parser = NonEmptyStringValueParser::new()
+	)]
+	redis_key_prefix: String,
+	/// Bind with SO_REUSEPORT and hand the socket over to a fresh process on SIGHUP; under systemd this
+	/// needs NotifyAccess=all, as the fresh process becomes the main one
+	#[arg(long, env = "TOUCHID_REUSE_PORT")]
+	reuse_port: bool,
+	/// Reject new locks with 507 once this many are stored
+	#[arg(long, env = "TOUCHID_MAX_LOCKS")]
+	max_locks: Option<usize>,
+	/// Reject new locks with 507 once their approximate footprint reaches this many bytes
+	#[arg(long, env = "TOUCHID_MAX_BYTES")]
+	max_bytes: Option<usize>,
+	/// Key casing of JSON bodies: snake or camel; with camel, requests may use either casing
+	#[arg(long, env = "TOUCHID_JSON_CASE", default_value = "snake")]
+	json_case: json::Case,
+	/// Pretty-print JSON responses; meant for development
+	#[arg(long, env = "TOUCHID_JSON_PRETTY")]
+	json_pretty: bool,
+	/// Reject request bodies containing unknown fields with 400 instead of ignoring them
+	#[arg(long, env = "TOUCHID_STRICT_JSON")]
+	strict_json: bool,
+	/// Latency objective as "<METHOD> <route> <ms>ms <target>%", e.g. "POST /lock/:id 50ms 99%"; repeatable
+	#[arg(long = "slo", env = "TOUCHID_SLOS", value_delimiter = ';')]
+	slos: Vec<slo::Objective>,
+	/// Bearer token required by the routes that change locks; repeatable to allow rotation
+	#[arg(
+		long = "api-token",
+		env = "TOUCHID_API_TOKENS",
+		value_delimiter = ',',
+		hide_env_values = true
+	)]
+	api_tokens: Vec<String>,
+	/// Require an API token for the read routes (lock state, analytics and usage) as well
+	#[arg(long, env = "TOUCHID_PRIVATE_READS", requires = "api_tokens")]
+	private_reads: bool,
+	/// Proxy address whose X-Forwarded-For header is believed when rate limiting and logging clients; repeatable
+	#[arg(
+		long = "trusted-proxy",
+		env = "TOUCHID_TRUSTED_PROXIES",
+		value_delimiter = ','
+	)]
+	trusted_proxies: Vec<IpAddr>,
+	/// Answer lock routes with 426 when the X-Client-Version header is older than this
+	#[arg(long, env = "TOUCHID_MIN_CLIENT_VERSION")]
+	min_client_version: Option<client_version::Version>,
+	/// Where outdated clients can get an update; included in 426 responses
+	#[arg(long, env = "TOUCHID_UPGRADE_URL", requires = "min_client_version")]
+	upgrade_url: Option<String>,
+	/// Share of the analytics events posted to /events/track that is kept, between 0 and 1
+	#[arg(long, env = "TOUCHID_EVENT_SAMPLE_RATE", default_value_t = 1.0, value_parser = sample_rate)]
+	event_sample_rate: f64,
+	/// Bearer token for the /admin routes, which are disabled when unset
+	#[arg(long, env = "TOUCHID_ADMIN_TOKEN", hide_env_values = true)]
+	admin_token: Option<String>,
+	/// Lifetime in seconds of locks whose request does not set a ttl; they never expire when unset
+	#[arg(long, env = "TOUCHID_DEFAULT_LOCK_TTL", value_parser = lock_ttl)]
+	default_lock_ttl: Option<u64>,
+	/// Log requests taking at least this many milliseconds, with a per-phase breakdown
+	#[arg(long, env = "TOUCHID_SLOW_REQUEST_MS")]
+	slow_request_ms: Option<u64>,
 }
 
-impl State {
-	pub fn new() -> Self {
-		Self::new_with_data(Arc::new(DashMap::new()))
+fn sample_rate(value: &str) -> Result<f64, String> {
+	match value.parse::<f64>() {
+		Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+		_ => Err("must be a number between 0 and 1".to_string()),
 	}
+}
 
-	pub fn new_with_data(data: Arc<DashMap<String, Lock>>) -> Self {
-		Self { locks: data }
+fn lock_ttl(value: &str) -> Result<u64, String> {
+	match value.parse::<u64>() {
+		Ok(ttl) if (1..=validate::MAX_TTL).contains(&ttl) => Ok(ttl),
+		_ => Err(format!(
+			"must be between 1 and {} seconds",
+			validate::MAX_TTL
+		)),
 	}
 }
 
-#[derive(Debug)]
-pub enum Error {
-	NotFound,
+#[derive(Subcommand)]
+enum BackupCommand {
+	/// Copy a data file into a standalone backup
+	Create {
+		#[arg(long, env = "TOUCHID_DATA")]
+		data: PathBuf,
+		out: PathBuf,
+	},
+	/// Check a backup's header and checksum without restoring it
+	Verify { file: PathBuf },
 }
 
-impl IntoResponse for Error {
-	fn into_response(self) -> axum::response::Response {
-		let status = match self {
-			Error::NotFound => StatusCode::GONE,
-		};
+#[derive(Subcommand)]
+enum TokenCommand {
+	/// Print a fresh random token suitable for `POST /lock/:id`
+	Issue,
+}
 
-		status.into_response()
-	}
+// what a `serve` process is handed by whoever started it
+struct Inherited {
+	listener: io::Result<Option<std::net::TcpListener>>,
+	predecessor: Option<reload::Predecessor>,
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Error> {
-	let addr: std::net::SocketAddr = std::net::SocketAddr::from(([0, 0, 0, 0], 3000));
+fn main() -> ExitCode {
+	// both read and then change the environment, which is only sound before the runtime starts
+	// threads that may read it at the same time
+	let inherited = Inherited {
+		listener: systemd::listener(),
+		predecessor: reload::predecessor(),
+	};
+	let runtime = match tokio::runtime::Builder::new_multi_thread()
+		.enable_all()
+		.build()
+	{
+		Ok(runtime) => runtime,
+		Err(e) => {
+			eprintln!("error: failed to start the runtime: {}", e);
 
-	axum::Server::bind(&addr)
-		.serve(router(State::new()).into_make_service())
-		.await
-		.unwrap();
+			return ExitCode::FAILURE;
+		}
+	};
 
-	Ok(())
+	runtime.block_on(start(inherited))
 }
 
-#[allow(dead_code)]
-fn router(state: State) -> Router {
-	Router::new()
-		.route("/lock/:id", post(lock))
-		.route("/unlock/:id", post(unlock))
-		.route("/purge", post(purge))
-		.with_state(state)
+async fn start(inherited: Inherited) -> ExitCode {
+	let log_filter = logging::init();
+	let result = match parse() {
+		Ok(cli) => run(cli, log_filter, inherited).await,
+		Err(e) => Err(e),
+	};
+
+	#[cfg(feature = "otlp")]
+	touchid::otlp::shutdown();
+
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(e) => {
+			eprintln!("error: {}", e);
+
+			ExitCode::FAILURE
+		}
+	}
 }
 
-pub async fn lock(
-	extract::State(state): extract::State<State>,
-	Path(id): Path<String>,
-	extract::Json(lock): extract::Json<Lock>,
-) -> Result<StatusCode, Error> {
-	state.locks.insert(id.clone(), lock.clone());
+// flags and environment first, then the config file for whatever they leave unset
+fn parse() -> Result<Cli, Box<dyn std::error::Error>> {
+	let mut command = Cli::command();
+	let matches = command.get_matches_mut();
+	let cli = Cli::from_arg_matches(&matches)?;
+	let (Command::Serve(args), Some(("serve", serve))) = (&cli.command, matches.subcommand())
+	else {
+		return Ok(cli);
+	};
+	let Some(path) = &args.config else {
+		return Ok(cli);
+	};
+	let serve_command = command
+		.find_subcommand("serve")
+		.expect("serve is a subcommand");
+	let extra = config::file_args(serve_command, serve, path)
+		.map_err(|e| format!("{}: {}", path.display(), e))?;
 
-	Ok(StatusCode::CREATED)
+	Ok(Cli::parse_from(
+		env::args_os().chain(extra.into_iter().map(Into::into)),
+	))
 }
 
-pub async fn unlock(
-	extract::State(state): extract::State<State>,
-	Path(id): Path<String>,
-) -> Result<(StatusCode, Json<Lock>), Error> {
-	if let Some((_, lock)) = state.locks.remove(&id) {
-		Ok((StatusCode::OK, Json(lock)))
-	} else {
-		Err(Error::NotFound)
+async fn run(
+	cli: Cli,
+	log_filter: logging::LogFilter,
+	inherited: Inherited,
+) -> Result<(), Box<dyn std::error::Error>> {
+	match cli.command {
+		Command::Serve(args) => serve(*args, log_filter, inherited).await?,
+		Command::Migrate { data } => {
+			let snapshot = Snapshot::decode(&fs::read(&data)?)?;
+			snapshot.save(&data)?;
+
+			println!(
+				"migrated {} locks from format v{} to v{}",
+				snapshot.locks.len(),
+				snapshot.version,
+				snapshot::VERSION
+			);
+		}
+		Command::Backup {
+			command: BackupCommand::Create { data, out },
+		} => {
+			let snapshot = Snapshot::decode(&fs::read(&data)?)?;
+			snapshot.save(&out)?;
+
+			println!(
+				"backed up {} locks to {}",
+				snapshot.locks.len(),
+				out.display()
+			);
+		}
+		Command::Backup {
+			command: BackupCommand::Verify { file },
+		} => {
+			let snapshot = Snapshot::decode(&fs::read(&file)?)?;
+
+			println!(
+				"{}: ok, format v{}, {} locks",
+				file.display(),
+				snapshot.version,
+				snapshot.locks.len()
+			);
+		}
+		Command::Restore { data, from } => {
+			let snapshot = Snapshot::decode(&fs::read(&from)?)?;
+			snapshot.save(&data)?;
+
+			println!(
+				"restored {} locks to {}",
+				snapshot.locks.len(),
+				data.display()
+			);
+		}
+		Command::Token {
+			command: TokenCommand::Issue,
+		} => println!("{}", lock::issue_token()),
 	}
+
+	Ok(())
 }
 
-pub async fn purge(extract::State(state): extract::State<State>) -> Result<StatusCode, Error> {
-	state.locks.clear();
+async fn open_store(args: &ServeArgs) -> Result<Arc<dyn LockStore>, Box<dyn std::error::Error>> {
+	match &args.database_url {
+		#[cfg(feature = "sqlite")]
+		Some(url) if url.starts_with("sqlite:") => Ok(Arc::new(
+			touchid::store::SqliteStore::connect(url, args.database_max_connections).await?,
+		)),
+		#[cfg(feature = "postgres")]
+		Some(url) if url.starts_with("postgres:") || url.starts_with("postgresql:") => Ok(Arc::new(
+			touchid::store::PostgresStore::connect(url, args.database_max_connections).await?,
+		)),
+		#[cfg(feature = "redis")]
+		Some(url) if url.starts_with("redis:") => Ok(Arc::new(
+			touchid::store::RedisStore::connect(url, args.redis_key_prefix.clone()).await?,
+		)),
+		Some(url) => Err(format!("unsupported database url: {}", url).into()),
+		None => {
+			let store = match &args.data {
+				Some(path) => MemoryStore::load(path.clone())?,
+				None => MemoryStore::new(Default::default()),
+			};
 
-	Ok(StatusCode::OK)
+			Ok(Arc::new(store.with_limits(Limits {
+				max_entries: args.max_locks,
+				max_bytes: args.max_bytes,
+			})))
+		}
+	}
+}
+
+async fn serve(
+	args: ServeArgs,
+	log_filter: logging::LogFilter,
+	inherited: Inherited,
+) -> Result<(), Box<dyn std::error::Error>> {
+	if let Some(level) = &args.log_level {
+		log_filter
+			.set(level)
+			.map_err(|e| format!("invalid log level '{}': {}", level, e))?;
+	}
+
+	let addr = SocketAddr::new(args.bind, args.port);
+	let listener = match inherited.listener? {
+		Some(listener) => listener,
+		None if args.reuse_port => reload::bind_reuse_port(addr)?,
+		None => std::net::TcpListener::bind(addr)?,
+	};
+	let drain = Arc::new(reload::Drain::default());
+	let server = axum::Server::builder(reload::Incoming::new(listener, drain.clone())?);
+
+	// a snapshot file has a single writer, so a successor loads it only once the predecessor has
+	// exited, connections queue in its backlog meanwhile; shared stores are taken over right away
+	let mut predecessor = inherited.predecessor;
+	let successor = predecessor.is_some();
+
+	if args.database_url.is_none() && args.data.is_some() {
+		if let Some(predecessor) = predecessor.take() {
+			predecessor.release()?;
+			predecessor.exited().await;
+		}
+	}
+
+	// the app is built and answers /healthz right away; everything else is refused until the store
+	// is open
+	let store = Arc::new(DeferredStore::default());
+	let gate = Arc::new(health::Gate::default());
+	let state = State::new_with_store(store.clone())
+		.with_json_format(json::Format {
+			case: args.json_case,
+			pretty: args.json_pretty,
+		})
+		.with_strict_bodies(args.strict_json)
+		.with_slos(args.slos.clone())
+		.with_admin_token(args.admin_token.clone())
+		.with_api_tokens(args.api_tokens.clone())
+		.with_private_reads(args.private_reads)
+		.with_trusted_proxies(args.trusted_proxies.clone())
+		.with_client_policy(args.min_client_version.map(|min| client_version::Policy {
+			min,
+			upgrade_url: args.upgrade_url.clone(),
+		}))
+		.with_slow_request_threshold(args.slow_request_ms.map(Duration::from_millis))
+		.with_default_lock_ttl(args.default_lock_ttl.map(Duration::from_secs))
+		.with_event_sample_rate(args.event_sample_rate)
+		.with_metrics(prometheus::install()?)
+		.with_log_filter(log_filter)
+		.with_startup_gate(gate.clone());
+	let reuse_port = args.reuse_port;
+	let server =
+		server.serve(router(state.clone()).into_make_service_with_connect_info::<SocketAddr>());
+	// stops accepting on a signal (or a hand-off to a successor) and lets in-flight requests finish
+	let server = server.with_graceful_shutdown(async move {
+		let signal = if reuse_port {
+			tokio::select! {
+				_ = reload::handed_off() => return drain.drain().await,
+				signal = shutdown::signal_received() => signal,
+			}
+		} else {
+			shutdown::signal_received().await
+		};
+
+		tracing::info!(signal, "shutting down, draining connections");
+		let _ = systemd::notify("STOPPING=1");
+	});
+	let startup = async move {
+		let opened = open_store(&args).await?;
+		store.set(opened.clone());
+
+		let info = version::info(&state);
+		tracing::info!(
+			version = info.version,
+			commit = info.git_commit,
+			built = info.build_timestamp,
+			features = ?info.features,
+			storage = info.storage,
+			%addr,
+			"touchid started"
+		);
+
+		expiry::spawn(state);
+		gate.open();
+
+		// the predecessor keeps accepting until this process can answer in its place
+		if let Some(predecessor) = predecessor {
+			predecessor.release()?;
+		}
+
+		if successor {
+			systemd::notify(&format!("MAINPID={}\nREADY=1", std::process::id()))?;
+		} else {
+			systemd::notify("READY=1")?;
+		}
+		systemd::spawn_watchdog();
+
+		Ok::<_, Box<dyn std::error::Error>>(opened)
+	};
+
+	tokio::pin!(server);
+	let store = if successor {
+		// nothing is accepted before the store is open, so that no request is refused meanwhile
+		startup.await?
+	} else {
+		tokio::select! {
+			store = startup => store?,
+			// stopped before the store was even open
+			result = &mut server => return Ok(result?),
+		}
+	};
+
+	server.await?;
+	store
+		.close()
+		.await
+		.map_err(|e| format!("failed to close the store: {:?}", e))?;
+	tracing::info!("touchid stopped");
+
+	Ok(())
 }
@@ -0,0 +1,122 @@
+use axum::{response::Html, Json};
+use utoipa::{
+	openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+	Modify, OpenApi,
+};
+
+use crate::{
+	admin, analytics, announcements, debug, dto, error, events, health, honeypot, lock_state,
+	logging, prometheus, status, validate, version,
+};
+
+// documents the default snake_case field names, whatever --json-case is set to
+#[derive(OpenApi)]
+#[openapi(
+	info(title = "touchid"),
+	paths(
+		crate::lock,
+		crate::issue,
+		crate::verify,
+		crate::unlock,
+		crate::relock,
+		crate::purge,
+		crate::usage,
+		lock_state::get,
+		analytics::occupancy,
+		events::track,
+		version::version,
+		health::healthz,
+		health::readyz,
+		prometheus::render,
+		status::status,
+		announcements::active,
+		announcements::list,
+		announcements::add,
+		announcements::remove,
+		status::open_incident,
+		status::resolve_incident,
+		admin::slo,
+		admin::runtime,
+		honeypot::hits,
+		logging::get_level,
+		logging::set_level,
+		debug::memory,
+	),
+	components(schemas(
+		analytics::Format,
+		dto::v1::LockRequest,
+		dto::v1::LockResponse,
+		dto::v1::IssueResponse,
+		dto::v1::VerifyRequest,
+		dto::v1::VerifyResponse,
+		error::ErrorBody,
+		validate::FieldError,
+	)),
+	modifiers(&Security, &Profiling),
+	tags(
+		(name = "locks"),
+		(name = "events", description = "anonymous usage events"),
+		(name = "service", description = "build, health and announcements"),
+		(name = "admin", description = "only mounted when an admin token is configured"),
+	)
+)]
+pub struct ApiDoc;
+
+struct Security;
+
+impl Modify for Security {
+	fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+		let components = openapi.components.get_or_insert_with(Default::default);
+
+		for name in ["api_token", "admin_token"] {
+			components.add_security_scheme(
+				name,
+				SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+			);
+		}
+	}
+}
+
+// the profiler route only exists in builds with the `pprof` feature
+#[cfg(feature = "pprof")]
+#[derive(OpenApi)]
+#[openapi(paths(debug::profile))]
+struct ProfilingDoc;
+
+struct Profiling;
+
+impl Modify for Profiling {
+	#[cfg(feature = "pprof")]
+	fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+		openapi.merge(ProfilingDoc::openapi());
+	}
+
+	#[cfg(not(feature = "pprof"))]
+	fn modify(&self, _: &mut utoipa::openapi::OpenApi) {}
+}
+
+// swagger ui is loaded from a cdn rather than bundled into the binary; pinned to an exact release
+// whose integrity hashes the browser checks, so that what the page runs only changes along with
+// this file
+const DOCS: &str = r##"<!doctype html>
+<html>
+<head>
+	<meta charset="utf-8">
+	<title>touchid</title>
+	<link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5.17.14/swagger-ui.css" integrity="sha384-wxLW6kwyHktdDGr6Pv1zgm/VGJh99lfUbzSn6HNHBENZlCN7W602k9VkGdxuFvPn" crossorigin="anonymous" referrerpolicy="no-referrer">
+</head>
+<body>
+	<div id="swagger-ui"></div>
+	<script src="https://unpkg.com/swagger-ui-dist@5.17.14/swagger-ui-bundle.js" integrity="sha384-wmyclcVGX/WhUkdkATwhaK1X1JtiNrr2EoYJ+diV3vj4v6OC5yCeSu+yW13SYJep" crossorigin="anonymous" referrerpolicy="no-referrer"></script>
+	<script>SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });</script>
+</body>
+</html>
+"##;
+
+pub async fn spec() -> Json<utoipa::openapi::OpenApi> {
+	Json(ApiDoc::openapi())
+}
+
+pub async fn docs() -> Html<&'static str> {
+	Html(DOCS)
+}
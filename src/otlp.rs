@@ -0,0 +1,80 @@
+use std::{env, sync::OnceLock};
+
+use axum::http::HeaderMap;
+use opentelemetry::{
+	global,
+	propagation::Extractor,
+	trace::{TraceError, TracerProvider as _},
+	KeyValue,
+};
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{
+	propagation::TraceContextPropagator, runtime, trace::TracerProvider, Resource,
+};
+use tracing::{Level, Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{filter::Targets, registry::LookupSpan, Layer};
+
+// the standard variable; the exporter reads it (and the other OTEL_EXPORTER_OTLP_* ones) itself
+const ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+const SERVICE_NAME: &str = "touchid";
+
+// kept to be shut down explicitly: the tracer held by the layer keeps it alive past any global reset
+static PROVIDER: OnceLock<TracerProvider> = OnceLock::new();
+
+// exports this crate's spans over otlp/grpc; None unless an endpoint is configured.
+// needs a tokio runtime, which batches and sends spans in the background
+pub fn layer<S>() -> Result<Option<impl Layer<S>>, TraceError>
+where
+	S: Subscriber + for<'span> LookupSpan<'span>,
+{
+	if env::var_os(ENDPOINT_ENV).is_none() {
+		return Ok(None);
+	}
+
+	let exporter = SpanExporter::builder().with_tonic().build()?;
+	let provider = TracerProvider::builder()
+		.with_batch_exporter(exporter, runtime::Tokio)
+		.with_resource(Resource::new([KeyValue::new("service.name", SERVICE_NAME)]))
+		.build();
+	let tracer = provider.tracer(SERVICE_NAME);
+
+	global::set_text_map_propagator(TraceContextPropagator::new());
+	global::set_tracer_provider(provider.clone());
+	let _ = PROVIDER.set(provider);
+
+	// only our own spans: the exporter's grpc client would otherwise trace itself
+	Ok(Some(
+		tracing_opentelemetry::layer()
+			.with_tracer(tracer)
+			.with_filter(Targets::new().with_target("touchid", Level::INFO)),
+	))
+}
+
+// sends whatever spans are still batched; call before the process exits
+pub fn shutdown() {
+	if let Some(provider) = PROVIDER.get() {
+		if let Err(e) = provider.shutdown() {
+			tracing::warn!(error = %e, "failed to flush spans");
+		}
+	}
+}
+
+struct Headers<'a>(&'a HeaderMap);
+
+impl Extractor for Headers<'_> {
+	fn get(&self, key: &str) -> Option<&str> {
+		self.0.get(key).and_then(|value| value.to_str().ok())
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		self.0.keys().map(|key| key.as_str()).collect()
+	}
+}
+
+// continues the trace of the caller when its request carries a `traceparent` header
+pub fn set_parent(span: &Span, headers: &HeaderMap) {
+	span.set_parent(global::get_text_map_propagator(|propagator| {
+		propagator.extract(&Headers(headers))
+	}));
+}
@@ -0,0 +1,52 @@
+use axum::{
+	async_trait,
+	extract::{self, rejection::PathRejection, FromRequestParts},
+	http::request::Parts,
+};
+use serde::de::DeserializeOwned;
+
+use crate::Error;
+
+// like axum's Path, but malformed segments are answered with the usual error body
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+	T: DeserializeOwned + Send,
+	S: Send + Sync,
+{
+	type Rejection = Error;
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+		match extract::Path::<T>::from_request_parts(parts, state).await {
+			Ok(extract::Path(value)) => Ok(Path(value)),
+			// a route declaring other parameters than its handler expects
+			Err(rejection @ PathRejection::MissingPathParams(_)) => {
+				tracing::error!(error = %rejection.body_text(), "path parameters do not match the route");
+
+				Err(Error::Internal)
+			}
+			Err(rejection) => Err(Error::BadRequest(rejection.body_text())),
+		}
+	}
+}
+
+// like axum's Query, but malformed query strings are answered with the usual error body
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+	T: DeserializeOwned,
+	S: Send + Sync,
+{
+	type Rejection = Error;
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+		extract::Query::<T>::from_request_parts(parts, state)
+			.await
+			.map(|extract::Query(value)| Query(value))
+			.map_err(|rejection| Error::BadRequest(rejection.body_text()))
+	}
+}
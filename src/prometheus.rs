@@ -0,0 +1,196 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+	extract::{self, MatchedPath},
+	http::{header, Request},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::{debug, runtime, Error, State};
+
+const REQUESTS: &str = "touchid_http_requests_total";
+const DURATION: &str = "touchid_http_request_duration_seconds";
+const IN_FLIGHT: &str = "touchid_http_requests_in_flight";
+const LOCKS: &str = "touchid_locks";
+const LOCK_BYTES: &str = "touchid_lock_bytes";
+const SLO_BURN_RATE: &str = "touchid_slo_burn_rate";
+const SLO_ALERTING: &str = "touchid_slo_alerting";
+const WORKERS: &str = "touchid_runtime_workers";
+const WORKER_BUSY: &str = "touchid_runtime_worker_busy_seconds";
+const WORKER_PARKS: &str = "touchid_runtime_worker_parks";
+const ALIVE_TASKS: &str = "touchid_runtime_alive_tasks";
+const GLOBAL_QUEUE_DEPTH: &str = "touchid_runtime_global_queue_depth";
+const RESIDENT_BYTES: &str = "touchid_resident_memory_bytes";
+const BUCKETS: &[f64] = &[
+	0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+// histograms are drained into their buckets on this interval even when nobody scrapes
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+// installs the process-wide recorder; can only succeed once per process
+pub fn install() -> Result<PrometheusHandle, BuildError> {
+	let handle = PrometheusBuilder::new()
+		.set_buckets_for_metric(Matcher::Full(DURATION.to_string()), BUCKETS)?
+		.install_recorder()?;
+
+	metrics::describe_counter!(REQUESTS, "Requests answered, by method, route and status");
+	metrics::describe_histogram!(
+		DURATION,
+		metrics::Unit::Seconds,
+		"Time to answer a request, by method and route"
+	);
+	metrics::describe_gauge!(IN_FLIGHT, "Requests being answered right now");
+	metrics::describe_gauge!(
+		LOCKS,
+		"Locks held by the store, as of the last expiry sweep; not reported for redis"
+	);
+	metrics::describe_gauge!(
+		LOCK_BYTES,
+		metrics::Unit::Bytes,
+		"Bytes of ids and tokens held by the store, as of the last expiry sweep; not reported for redis"
+	);
+	metrics::describe_gauge!(
+		SLO_BURN_RATE,
+		"Rate at which a latency objective burns its error budget, by method, route and window"
+	);
+	metrics::describe_gauge!(SLO_ALERTING, "1 while a latency objective burns too fast");
+	metrics::describe_gauge!(WORKERS, "Tokio worker threads");
+	metrics::describe_gauge!(
+		WORKER_BUSY,
+		metrics::Unit::Seconds,
+		"Time a tokio worker has spent busy since startup, by worker"
+	);
+	metrics::describe_gauge!(WORKER_PARKS, "Times a tokio worker has parked, by worker");
+	metrics::describe_gauge!(ALIVE_TASKS, "Tokio tasks not yet finished");
+	metrics::describe_gauge!(
+		GLOBAL_QUEUE_DEPTH,
+		"Tasks waiting in the tokio global queue"
+	);
+	metrics::describe_gauge!(
+		RESIDENT_BYTES,
+		metrics::Unit::Bytes,
+		"Resident memory of the process"
+	);
+
+	let upkeep = handle.clone();
+	tokio::spawn(async move {
+		let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+
+		loop {
+			interval.tick().await;
+			upkeep.run_upkeep();
+		}
+	});
+
+	Ok(handle)
+}
+
+// decrements on drop, so requests whose client went away are not counted forever
+struct InFlight(metrics::Gauge);
+
+impl Drop for InFlight {
+	fn drop(&mut self) {
+		self.0.decrement(1);
+	}
+}
+
+// labelled by route template rather than path so that ids do not blow up the series count
+pub async fn track<B>(route: Option<MatchedPath>, req: Request<B>, next: Next<B>) -> Response {
+	let Some(route) = route else {
+		return next.run(req).await;
+	};
+	let method = req.method().to_string();
+	let route = route.as_str().to_string();
+	let in_flight = InFlight(metrics::gauge!(IN_FLIGHT));
+	in_flight.0.increment(1);
+
+	let started = Instant::now();
+	let res = next.run(req).await;
+	drop(in_flight);
+
+	metrics::histogram!(DURATION, "method" => method.clone(), "route" => route.clone())
+		.record(started.elapsed());
+	metrics::counter!(
+		REQUESTS,
+		"method" => method,
+		"route" => route,
+		"status" => res.status().as_u16().to_string()
+	)
+	.increment(1);
+
+	res
+}
+
+// counting what the store holds can mean a full scan, so the expiry sweep does it now and then
+// rather than every scrape; stores that can only count by walking every key are left out
+pub async fn record_usage(state: &State) {
+	if state.metrics.is_none() || !state.store.usage_is_cheap() {
+		return;
+	}
+
+	match state.store.usage().await {
+		Ok(usage) => {
+			metrics::gauge!(LOCKS).set(usage.entries as f64);
+			metrics::gauge!(LOCK_BYTES).set(usage.bytes as f64);
+		}
+		Err(e) => tracing::warn!(error = ?e, "failed to measure the store"),
+	}
+}
+
+// the rest is cheap and read at scrape time
+fn record_slos(state: &State) {
+	for slo in state.slo.report() {
+		for (window, rate) in [("5m", slo.burn_rate_5m), ("1h", slo.burn_rate_1h)] {
+			metrics::gauge!(
+				SLO_BURN_RATE,
+				"method" => slo.method.clone(),
+				"route" => slo.route.clone(),
+				"window" => window
+			)
+			.set(rate);
+		}
+
+		metrics::gauge!(SLO_ALERTING, "method" => slo.method, "route" => slo.route)
+			.set(if slo.alerting { 1.0 } else { 0.0 });
+	}
+}
+
+fn record_runtime() {
+	let report = runtime::report();
+
+	metrics::gauge!(WORKERS).set(report.workers.len() as f64);
+	metrics::gauge!(ALIVE_TASKS).set(report.alive_tasks as f64);
+	metrics::gauge!(GLOBAL_QUEUE_DEPTH).set(report.global_queue_depth as f64);
+
+	for (i, worker) in report.workers.iter().enumerate() {
+		let id = i.to_string();
+
+		metrics::gauge!(WORKER_BUSY, "worker" => id.clone()).set(worker.busy_ms as f64 / 1000.0);
+		metrics::gauge!(WORKER_PARKS, "worker" => id).set(worker.parks as f64);
+	}
+}
+
+#[utoipa::path(
+	get,
+	path = "/metrics",
+	tag = "service",
+	responses((status = 200, description = "metrics in the prometheus text format", body = String, content_type = "text/plain")),
+)]
+pub async fn render(extract::State(state): extract::State<State>) -> Result<Response, Error> {
+	let handle = state.metrics.as_ref().ok_or(Error::NotFound)?;
+
+	record_slos(&state);
+	record_runtime();
+
+	if let Some(bytes) = debug::resident_bytes() {
+		metrics::gauge!(RESIDENT_BYTES).set(bytes as f64);
+	}
+
+	Ok((
+		[(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+		handle.render(),
+	)
+		.into_response())
+}
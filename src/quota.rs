@@ -0,0 +1,84 @@
+use std::{
+	mem,
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde::{self, Serialize};
+use utoipa::ToSchema;
+
+use crate::lock::Lock;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Limits {
+	pub max_entries: Option<usize>,
+	pub max_bytes: Option<usize>,
+}
+
+#[derive(Default, Debug)]
+pub struct Usage {
+	entries: AtomicUsize,
+	bytes: AtomicUsize,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+#[schema(as = UsageReport)]
+pub struct Report {
+	pub entries: usize,
+	pub bytes: usize,
+	pub max_entries: Option<usize>,
+	pub max_bytes: Option<usize>,
+}
+
+// approximate heap + inline footprint of a map entry
+pub fn footprint(id: &str, lock: &Lock) -> usize {
+	mem::size_of::<String>() + mem::size_of::<Lock>() + id.len() + lock.token.len()
+}
+
+fn reserve(counter: &AtomicUsize, amount: usize, max: Option<usize>) -> bool {
+	counter
+		.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+			let next = current.checked_add(amount)?;
+
+			match max {
+				Some(max) if next > max => None,
+				_ => Some(next),
+			}
+		})
+		.is_ok()
+}
+
+impl Usage {
+	pub fn try_reserve(&self, limits: &Limits, entries: usize, bytes: usize) -> bool {
+		if !reserve(&self.entries, entries, limits.max_entries) {
+			return false;
+		}
+
+		if !reserve(&self.bytes, bytes, limits.max_bytes) {
+			self.entries.fetch_sub(entries, Ordering::SeqCst);
+
+			return false;
+		}
+
+		true
+	}
+
+	pub fn record(&self, entries: usize, bytes: usize) {
+		self.entries.fetch_add(entries, Ordering::SeqCst);
+		self.bytes.fetch_add(bytes, Ordering::SeqCst);
+	}
+
+	pub fn release(&self, entries: usize, bytes: usize) {
+		self.entries.fetch_sub(entries, Ordering::SeqCst);
+		self.bytes.fetch_sub(bytes, Ordering::SeqCst);
+	}
+
+	pub fn report(&self, limits: &Limits) -> Report {
+		Report {
+			entries: self.entries.load(Ordering::SeqCst),
+			bytes: self.bytes.load(Ordering::SeqCst),
+			max_entries: limits.max_entries,
+			max_bytes: limits.max_bytes,
+		}
+	}
+}
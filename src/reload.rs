@@ -0,0 +1,209 @@
+use std::{
+	env, fs, io,
+	net::{SocketAddr, TcpListener},
+	os::unix::{net, process::parent_id},
+	path::PathBuf,
+	pin::Pin,
+	process,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	task::{Context, Poll},
+	time::Duration,
+};
+
+use hyper::server::{
+	accept::Accept,
+	conn::{AddrIncoming, AddrStream},
+};
+use socket2::{Domain, Socket, Type};
+use tokio::{
+	net::UnixDatagram,
+	process::Command,
+	signal::unix::{signal, SignalKind},
+	sync::Notify,
+	time,
+};
+
+// set on the successor so it knows which process to release once it can take over
+const PREDECESSOR_ENV: &str = "TOUCHID_PREDECESSOR_PID";
+// the service manager's socket; NOTIFY_SOCKET points at the predecessor until the hand-off is done
+const SERVICE_NOTIFY_ENV: &str = "TOUCHID_SERVICE_NOTIFY_SOCKET";
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub fn bind_reuse_port(addr: SocketAddr) -> io::Result<TcpListener> {
+	let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+
+	socket.set_reuse_address(true)?;
+	socket.set_reuse_port(true)?;
+	socket.bind(&addr.into())?;
+	socket.listen(1024)?;
+	socket.set_nonblocking(true)?;
+
+	Ok(socket.into())
+}
+
+// set when a successor has taken over: whatever is already queued on the listener is accepted, and
+// only then is it reported drained, so closing it resets none of the connections it had queued
+#[derive(Default, Debug)]
+pub struct Drain {
+	draining: AtomicBool,
+	drained: Notify,
+}
+
+impl Drain {
+	pub async fn drain(&self) {
+		self.draining.store(true, Ordering::SeqCst);
+		self.drained.notified().await;
+	}
+}
+
+pub struct Incoming {
+	inner: AddrIncoming,
+	drain: Arc<Drain>,
+}
+
+impl Incoming {
+	pub fn new(listener: TcpListener, drain: Arc<Drain>) -> io::Result<Self> {
+		listener.set_nonblocking(true)?;
+		let inner = AddrIncoming::from_listener(tokio::net::TcpListener::from_std(listener)?)
+			.map_err(io::Error::other)?;
+
+		Ok(Self { inner, drain })
+	}
+}
+
+impl Accept for Incoming {
+	type Conn = AddrStream;
+	type Error = io::Error;
+
+	fn poll_accept(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+	) -> Poll<Option<io::Result<AddrStream>>> {
+		let polled = Pin::new(&mut self.inner).poll_accept(cx);
+
+		if polled.is_pending() && self.drain.draining.load(Ordering::SeqCst) {
+			self.drain.drained.notify_one();
+		}
+
+		polled
+	}
+}
+
+// resolves once a successor is serving on the shared port; retries on every SIGHUP until one starts
+pub async fn handed_off() {
+	let mut hangup = signal(SignalKind::hangup()).expect("failed to install SIGHUP handler");
+
+	loop {
+		hangup.recv().await;
+
+		match spawn_successor().await {
+			Ok(pid) => {
+				tracing::info!(pid, "handed off to successor, draining");
+
+				return;
+			}
+			Err(e) => tracing::error!(error = %e, "reload failed"),
+		}
+	}
+}
+
+// starts a copy of this binary with the same arguments and waits for it to release this one
+async fn spawn_successor() -> io::Result<u32> {
+	let path = env::temp_dir().join(format!("touchid-handoff-{}.sock", process::id()));
+	let _ = fs::remove_file(&path);
+	let socket = UnixDatagram::bind(&path)?;
+	let result = wait_for_successor(&socket, &path).await;
+	let _ = fs::remove_file(&path);
+
+	result
+}
+
+async fn wait_for_successor(socket: &UnixDatagram, path: &PathBuf) -> io::Result<u32> {
+	let mut command = Command::new(env::current_exe()?);
+	command
+		.args(env::args_os().skip(1))
+		.env("NOTIFY_SOCKET", path)
+		.env(PREDECESSOR_ENV, process::id().to_string());
+
+	if let Some(socket) = env::var_os("NOTIFY_SOCKET") {
+		command.env(SERVICE_NOTIFY_ENV, socket);
+	}
+
+	let mut child = command.spawn()?;
+	let pid = child.id().unwrap_or_default();
+	let mut buf = [0u8; 1024];
+
+	let ready = async {
+		loop {
+			let len = socket.recv(&mut buf).await?;
+
+			if String::from_utf8_lossy(&buf[..len])
+				.lines()
+				.any(|line| line == "READY=1")
+			{
+				return Ok::<_, io::Error>(());
+			}
+		}
+	};
+
+	tokio::select! {
+		ready = time::timeout(READY_TIMEOUT, ready) => match ready {
+			Ok(Ok(())) => Ok(pid),
+			Ok(Err(e)) => Err(e),
+			Err(_) => {
+				let _ = child.kill().await;
+
+				Err(io::Error::new(io::ErrorKind::TimedOut, "successor never became ready"))
+			}
+		},
+		status = child.wait() => Err(io::Error::other(format!(
+			"successor exited early: {}",
+			status?
+		))),
+	}
+}
+
+// the process a successor was started by; it keeps serving until released
+#[derive(Debug)]
+pub struct Predecessor {
+	pid: u32,
+	socket: PathBuf,
+}
+
+// on a successor: points NOTIFY_SOCKET back at the service manager, so that the successor can take
+// over as its main process, and returns the predecessor waiting to be released. it changes the
+// environment, so it must be called before any other thread is started
+pub fn predecessor() -> Option<Predecessor> {
+	let pid = env::var(PREDECESSOR_ENV)
+		.ok()
+		.and_then(|pid| pid.parse::<u32>().ok())?;
+	let socket = env::var_os("NOTIFY_SOCKET")?.into();
+
+	env::remove_var(PREDECESSOR_ENV);
+	match env::var_os(SERVICE_NOTIFY_ENV) {
+		Some(service) => env::set_var("NOTIFY_SOCKET", service),
+		None => env::remove_var("NOTIFY_SOCKET"),
+	}
+	env::remove_var(SERVICE_NOTIFY_ENV);
+
+	Some(Predecessor { pid, socket })
+}
+
+impl Predecessor {
+	// tells the predecessor to stop accepting; it drains its queue and in-flight requests, then exits
+	pub fn release(&self) -> io::Result<()> {
+		net::UnixDatagram::unbound()?.send_to(b"READY=1", &self.socket)?;
+
+		Ok(())
+	}
+
+	pub async fn exited(self) {
+		while parent_id() == self.pid {
+			time::sleep(POLL_INTERVAL).await;
+		}
+	}
+}
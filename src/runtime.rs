@@ -0,0 +1,59 @@
+use serde::{self, Serialize};
+use tokio::runtime::Handle;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct Worker {
+	pub busy_ms: u128,
+	pub parks: u64,
+	#[cfg(tokio_unstable)]
+	pub polls: u64,
+	#[cfg(tokio_unstable)]
+	pub mean_poll_us: u128,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+#[schema(as = RuntimeReport)]
+pub struct Report {
+	pub workers: Vec<Worker>,
+	pub alive_tasks: usize,
+	pub global_queue_depth: usize,
+	// the remaining counters are only tracked by builds with RUSTFLAGS="--cfg tokio_unstable"
+	#[cfg(tokio_unstable)]
+	pub spawned_tasks: u64,
+	#[cfg(tokio_unstable)]
+	pub blocking_threads: usize,
+	#[cfg(tokio_unstable)]
+	pub idle_blocking_threads: usize,
+	#[cfg(tokio_unstable)]
+	pub blocking_queue_depth: usize,
+}
+
+pub fn report() -> Report {
+	let metrics = Handle::current().metrics();
+
+	Report {
+		workers: (0..metrics.num_workers())
+			.map(|worker| Worker {
+				busy_ms: metrics.worker_total_busy_duration(worker).as_millis(),
+				parks: metrics.worker_park_count(worker),
+				#[cfg(tokio_unstable)]
+				polls: metrics.worker_poll_count(worker),
+				#[cfg(tokio_unstable)]
+				mean_poll_us: metrics.worker_mean_poll_time(worker).as_micros(),
+			})
+			.collect(),
+		alive_tasks: metrics.num_alive_tasks(),
+		global_queue_depth: metrics.global_queue_depth(),
+		#[cfg(tokio_unstable)]
+		spawned_tasks: metrics.spawned_tasks_count(),
+		#[cfg(tokio_unstable)]
+		blocking_threads: metrics.num_blocking_threads(),
+		#[cfg(tokio_unstable)]
+		idle_blocking_threads: metrics.num_idle_blocking_threads(),
+		#[cfg(tokio_unstable)]
+		blocking_queue_depth: metrics.blocking_queue_depth(),
+	}
+}
@@ -0,0 +1,12 @@
+use tokio::signal::unix::{signal, SignalKind};
+
+// resolves on the first SIGINT (ctrl-c) or SIGTERM (docker stop, kubernetes, systemd), with its name
+pub async fn signal_received() -> &'static str {
+	let mut interrupt = signal(SignalKind::interrupt()).expect("failed to install SIGINT handler");
+	let mut terminate = signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+
+	tokio::select! {
+		_ = interrupt.recv() => "SIGINT",
+		_ = terminate.recv() => "SIGTERM",
+	}
+}
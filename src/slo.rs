@@ -0,0 +1,238 @@
+use std::{
+	collections::VecDeque,
+	str::FromStr,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+use axum::{
+	extract::{self, MatchedPath},
+	http::{Method, Request},
+	middleware::Next,
+	response::Response,
+};
+use serde::{self, Serialize};
+use utoipa::ToSchema;
+
+use crate::State;
+
+const BUCKET: Duration = Duration::from_secs(60);
+const SHORT_WINDOW: u64 = 5;
+const LONG_WINDOW: u64 = 60;
+// burning 2% of a 30 day budget within an hour; see the SRE workbook on multiwindow alerts
+const FAST_BURN: f64 = 14.4;
+
+#[derive(Clone, Debug)]
+pub struct Objective {
+	pub method: Method,
+	pub route: String,
+	pub threshold: Duration,
+	pub target: f64,
+}
+
+// parses "<METHOD> <route> <threshold>ms <target>%", e.g. "POST /lock/:id 50ms 99%"
+impl FromStr for Objective {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let parts: Vec<&str> = s.split_whitespace().collect();
+		let [method, route, threshold, target] = parts[..] else {
+			return Err(format!(
+				"expected '<METHOD> <route> <ms>ms <target>%', got '{}'",
+				s
+			));
+		};
+		let method = method
+			.to_uppercase()
+			.parse::<Method>()
+			.map_err(|e| e.to_string())?;
+		let threshold = threshold
+			.strip_suffix("ms")
+			.and_then(|ms| ms.parse::<u64>().ok())
+			.map(Duration::from_millis)
+			.ok_or_else(|| format!("invalid threshold '{}'", threshold))?;
+		let target = target
+			.strip_suffix('%')
+			.and_then(|pct| pct.parse::<f64>().ok())
+			.filter(|pct| *pct > 0.0 && *pct < 100.0)
+			.ok_or_else(|| format!("invalid target '{}'", target))?;
+
+		Ok(Self {
+			method,
+			route: route.to_string(),
+			threshold,
+			target: target / 100.0,
+		})
+	}
+}
+
+#[derive(Default, Debug)]
+struct Bucket {
+	minute: u64,
+	good: u64,
+	total: u64,
+}
+
+#[derive(Debug)]
+pub struct Tracker {
+	started: Instant,
+	objectives: Vec<(Objective, Mutex<VecDeque<Bucket>>)>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+#[schema(as = SloStatus)]
+pub struct Status {
+	pub method: String,
+	pub route: String,
+	pub threshold_ms: u128,
+	pub target: f64,
+	pub requests_1h: u64,
+	pub burn_rate_5m: f64,
+	pub burn_rate_1h: f64,
+	pub alerting: bool,
+}
+
+impl Default for Tracker {
+	fn default() -> Self {
+		Self::new(Vec::new())
+	}
+}
+
+impl Tracker {
+	pub fn new(objectives: Vec<Objective>) -> Self {
+		Self {
+			started: Instant::now(),
+			objectives: objectives
+				.into_iter()
+				.map(|objective| (objective, Mutex::new(VecDeque::new())))
+				.collect(),
+		}
+	}
+
+	fn minute(&self) -> u64 {
+		self.started.elapsed().as_secs() / BUCKET.as_secs()
+	}
+
+	pub fn record(&self, method: &Method, route: &str, elapsed: Duration) {
+		let minute = self.minute();
+
+		for (objective, buckets) in &self.objectives {
+			if objective.method != method || objective.route != route {
+				continue;
+			}
+
+			let mut buckets = buckets.lock().unwrap();
+
+			if buckets.back().is_none_or(|bucket| bucket.minute != minute) {
+				buckets.push_back(Bucket {
+					minute,
+					..Default::default()
+				});
+			}
+
+			while buckets
+				.front()
+				.is_some_and(|bucket| bucket.minute + LONG_WINDOW <= minute)
+			{
+				buckets.pop_front();
+			}
+
+			let bucket = buckets.back_mut().unwrap();
+			bucket.total += 1;
+
+			if elapsed <= objective.threshold {
+				bucket.good += 1;
+			}
+		}
+	}
+
+	pub fn report(&self) -> Vec<Status> {
+		let minute = self.minute();
+
+		self.objectives
+			.iter()
+			.map(|(objective, buckets)| {
+				let buckets = buckets.lock().unwrap();
+				let window = |len: u64| {
+					buckets
+						.iter()
+						.filter(|bucket| bucket.minute + len > minute)
+						.fold((0, 0), |(good, total), bucket| {
+							(good + bucket.good, total + bucket.total)
+						})
+				};
+				let burn_rate = |(good, total): (u64, u64)| {
+					if total == 0 {
+						0.0
+					} else {
+						(1.0 - good as f64 / total as f64) / (1.0 - objective.target)
+					}
+				};
+				let long = window(LONG_WINDOW);
+				let burn_rate_5m = burn_rate(window(SHORT_WINDOW));
+				let burn_rate_1h = burn_rate(long);
+
+				Status {
+					method: objective.method.to_string(),
+					route: objective.route.clone(),
+					threshold_ms: objective.threshold.as_millis(),
+					target: objective.target,
+					requests_1h: long.1,
+					burn_rate_5m,
+					burn_rate_1h,
+					alerting: burn_rate_5m > FAST_BURN && burn_rate_1h > FAST_BURN,
+				}
+			})
+			.collect()
+	}
+}
+
+pub async fn track<B>(
+	extract::State(state): extract::State<State>,
+	route: Option<MatchedPath>,
+	req: Request<B>,
+	next: Next<B>,
+) -> Response {
+	let method = req.method().clone();
+	let started = Instant::now();
+	let res = next.run(req).await;
+
+	if let Some(route) = route {
+		state.slo.record(&method, route.as_str(), started.elapsed());
+	}
+
+	res
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn objectives_parse() {
+		let objective: Objective = "post /lock/:id 50ms 99.9%".parse().unwrap();
+
+		assert_eq!(objective.method, Method::POST);
+		assert_eq!(objective.route, "/lock/:id");
+		assert_eq!(objective.threshold, Duration::from_millis(50));
+		assert!((objective.target - 0.999).abs() < 1e-9);
+	}
+
+	#[test]
+	fn malformed_objectives_are_rejected() {
+		for s in [
+			"",
+			"POST /lock/:id 50ms",
+			"POST /lock/:id 50ms 99% extra",
+			"POST /lock/:id 50 99%",
+			"POST /lock/:id 0.5ms 99%",
+			"POST /lock/:id 50ms 99",
+			"POST /lock/:id 50ms 100%",
+			"POST /lock/:id 50ms 0%",
+			"P OST /lock/:id 50ms 99%",
+		] {
+			assert!(s.parse::<Objective>().is_err(), "{}", s);
+		}
+	}
+}
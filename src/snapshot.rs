@@ -0,0 +1,248 @@
+use std::{
+	collections::HashMap,
+	fmt,
+	fs::{self, File},
+	io::{self, Write},
+	path::{Path, PathBuf},
+};
+
+use serde::{self, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::lock::Lock;
+
+// v1: plain json {"version": 1, "locks": {..}}
+// v2: MAGIC | version (u32 le) | sha256 of the payload | zstd-compressed json of the locks
+pub const VERSION: u32 = 2;
+const MAGIC: &[u8; 4] = b"TIDS";
+const HEADER_LEN: usize = 4 + 4 + 32;
+const COMPRESSION_LEVEL: i32 = 3;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "self::serde")]
+pub struct Snapshot {
+	// the format version the snapshot was decoded from; always encoded as VERSION
+	pub version: u32,
+	pub locks: HashMap<String, Lock>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io(io::Error),
+	Malformed(serde_json::Error),
+	Truncated,
+	ChecksumMismatch,
+	UnsupportedVersion(u32),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::Io(e) => write!(f, "io error: {}", e),
+			Error::Malformed(e) => write!(f, "malformed snapshot: {}", e),
+			Error::Truncated => write!(f, "snapshot is truncated"),
+			Error::ChecksumMismatch => write!(f, "snapshot checksum mismatch"),
+			Error::UnsupportedVersion(v) => write!(f, "unsupported snapshot version {}", v),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Self {
+		Error::Io(e)
+	}
+}
+
+impl From<serde_json::Error> for Error {
+	fn from(e: serde_json::Error) -> Self {
+		Error::Malformed(e)
+	}
+}
+
+impl Default for Snapshot {
+	fn default() -> Self {
+		Self::new(HashMap::new())
+	}
+}
+
+impl Snapshot {
+	pub fn new(locks: HashMap<String, Lock>) -> Self {
+		Self {
+			version: VERSION,
+			locks,
+		}
+	}
+
+	pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+		if !bytes.starts_with(MAGIC) {
+			return Self::decode_v1(bytes);
+		}
+
+		if bytes.len() < HEADER_LEN {
+			return Err(Error::Truncated);
+		}
+
+		let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+
+		if version != VERSION {
+			return Err(Error::UnsupportedVersion(version));
+		}
+
+		let payload = zstd::decode_all(&bytes[HEADER_LEN..])?;
+
+		if Sha256::digest(&payload)[..] != bytes[8..HEADER_LEN] {
+			return Err(Error::ChecksumMismatch);
+		}
+
+		Ok(Self {
+			version,
+			locks: serde_json::from_slice(&payload)?,
+		})
+	}
+
+	fn decode_v1(bytes: &[u8]) -> Result<Self, Error> {
+		let snapshot: Snapshot = serde_json::from_slice(bytes)?;
+
+		if snapshot.version != 1 {
+			Err(Error::UnsupportedVersion(snapshot.version))
+		} else {
+			Ok(snapshot)
+		}
+	}
+
+	pub fn encode(&self) -> Result<Vec<u8>, Error> {
+		let payload = serde_json::to_vec(&self.locks)?;
+		let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len() / 2);
+
+		bytes.extend_from_slice(MAGIC);
+		bytes.extend_from_slice(&VERSION.to_le_bytes());
+		bytes.extend_from_slice(&Sha256::digest(&payload));
+		bytes.extend(zstd::encode_all(&payload[..], COMPRESSION_LEVEL)?);
+
+		Ok(bytes)
+	}
+
+	// a missing file is treated as an empty store so that a fresh deployment can point at a new path
+	pub fn load(path: &Path) -> Result<Self, Error> {
+		match fs::read(path) {
+			Ok(bytes) => Self::decode(&bytes),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	// synced before and after the rename, so that a crash leaves either the old or the new file
+	pub fn save(&self, path: &Path) -> Result<(), Error> {
+		let tmp = tmp_path(path);
+		let mut file = File::create(&tmp)?;
+
+		file.write_all(&self.encode()?)?;
+		file.sync_all()?;
+		fs::rename(&tmp, path)?;
+
+		let dir = match path.parent() {
+			Some(dir) if !dir.as_os_str().is_empty() => dir,
+			_ => Path::new("."),
+		};
+		File::open(dir)?.sync_all()?;
+
+		Ok(())
+	}
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+	let mut name = path.file_name().unwrap_or_default().to_os_string();
+	name.push(".tmp");
+
+	path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn snapshot() -> Snapshot {
+		Snapshot::new(HashMap::from([
+			(
+				"door".to_string(),
+				Lock {
+					token: "tok".to_string(),
+					expires_at: Some(1_700_000_000),
+					uses_left: Some(2),
+				},
+			),
+			(
+				"gate".to_string(),
+				Lock {
+					token: "other".to_string(),
+					expires_at: None,
+					uses_left: None,
+				},
+			),
+		]))
+	}
+
+	#[test]
+	fn v1_files_still_decode() {
+		let bytes = br#"{"version":1,"locks":{"door":{"token":"tok","expires_at":1700000000}}}"#;
+		let decoded = Snapshot::decode(bytes).unwrap();
+
+		assert_eq!(decoded.version, 1);
+		assert_eq!(decoded.locks["door"].token, "tok");
+		assert_eq!(decoded.locks["door"].expires_at, Some(1_700_000_000));
+		assert_eq!(decoded.locks["door"].uses_left, None);
+	}
+
+	#[test]
+	fn v2_round_trips() {
+		let bytes = snapshot().encode().unwrap();
+		let decoded = Snapshot::decode(&bytes).unwrap();
+
+		assert!(bytes.starts_with(MAGIC));
+		assert_eq!(decoded.version, VERSION);
+		assert_eq!(decoded.locks, snapshot().locks);
+	}
+
+	#[test]
+	fn truncated_files_are_rejected() {
+		let bytes = snapshot().encode().unwrap();
+
+		assert!(matches!(
+			Snapshot::decode(&bytes[..HEADER_LEN - 1]),
+			Err(Error::Truncated)
+		));
+		// a cut in the compressed payload surfaces from zstd
+		assert!(matches!(
+			Snapshot::decode(&bytes[..bytes.len() - 4]),
+			Err(Error::Io(_))
+		));
+	}
+
+	#[test]
+	fn corrupted_files_are_rejected() {
+		let mut bytes = snapshot().encode().unwrap();
+		bytes[8] ^= 0xff;
+		assert!(matches!(
+			Snapshot::decode(&bytes),
+			Err(Error::ChecksumMismatch)
+		));
+
+		let mut bytes = snapshot().encode().unwrap();
+		bytes[4..8].copy_from_slice(&3u32.to_le_bytes());
+		assert!(matches!(
+			Snapshot::decode(&bytes),
+			Err(Error::UnsupportedVersion(3))
+		));
+
+		assert!(matches!(
+			Snapshot::decode(br#"{"version":7,"locks":{}}"#),
+			Err(Error::UnsupportedVersion(7))
+		));
+		assert!(matches!(
+			Snapshot::decode(b"not a snapshot"),
+			Err(Error::Malformed(_))
+		));
+	}
+}
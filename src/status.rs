@@ -0,0 +1,250 @@
+use std::{
+	net::{IpAddr, SocketAddr},
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+use axum::{
+	extract::{self, ConnectInfo},
+	http::{header, HeaderMap, HeaderValue, StatusCode},
+	response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use serde::{self, Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+	honeypot,
+	json::{Body, Reply},
+	lock,
+	params::Path,
+	Error, State,
+};
+
+const CACHE_TTL: Duration = Duration::from_secs(10);
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const RATE_LIMIT: u32 = 60;
+// clients tracked at once; new ones are turned away while the table is full
+const MAX_CLIENTS: usize = 10_000;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug, ToSchema)]
+#[serde(crate = "self::serde", rename_all = "lowercase")]
+pub enum Health {
+	Operational,
+	Degraded,
+}
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct Incident {
+	pub id: u64,
+	pub message: String,
+	pub since: u64,
+}
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+#[schema(as = StatusReport)]
+pub struct Report {
+	pub status: Health,
+	pub updated_at: u64,
+	pub incidents: Vec<Incident>,
+}
+
+// backs the public status page; reports are rebuilt at most every CACHE_TTL
+#[derive(Default, Debug)]
+pub struct Board {
+	incidents: Mutex<Vec<Incident>>,
+	next_id: AtomicU64,
+	cached: Mutex<Option<(Instant, Report)>>,
+	clients: DashMap<IpAddr, (Instant, u32)>,
+}
+
+impl Board {
+	pub fn open(&self, message: String) -> Incident {
+		let incident = Incident {
+			id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+			message,
+			since: lock::now(),
+		};
+
+		self.incidents.lock().unwrap().push(incident.clone());
+		self.cached.lock().unwrap().take();
+
+		incident
+	}
+
+	pub fn resolve(&self, id: u64) -> bool {
+		let mut incidents = self.incidents.lock().unwrap();
+		let before = incidents.len();
+		incidents.retain(|incident| incident.id != id);
+		self.cached.lock().unwrap().take();
+
+		incidents.len() != before
+	}
+
+	// fixed one-minute windows per client address
+	fn allow(&self, ip: IpAddr) -> bool {
+		let now = Instant::now();
+		let mut client = match self.clients.get_mut(&ip) {
+			Some(client) => client,
+			None if self.clients.len() >= MAX_CLIENTS => return false,
+			None => self.clients.entry(ip).or_insert((now, 0)),
+		};
+		let (started, count) = client.value_mut();
+
+		if now.duration_since(*started) >= RATE_WINDOW {
+			*started = now;
+			*count = 0;
+		}
+
+		*count += 1;
+
+		*count <= RATE_LIMIT
+	}
+
+	// drops clients whose window has passed; run by the expiry sweep
+	pub fn prune(&self) {
+		let now = Instant::now();
+
+		self.clients
+			.retain(|_, (started, _)| now.duration_since(*started) < RATE_WINDOW);
+	}
+}
+
+// coarse on purpose: storage that answers and no slo burning its error budget
+async fn check(state: &State) -> Health {
+	let storage_ok = state.store.ping().await.is_ok();
+	let slos_ok = state.slo.report().iter().all(|slo| !slo.alerting);
+
+	if storage_ok && slos_ok {
+		Health::Operational
+	} else {
+		Health::Degraded
+	}
+}
+
+async fn report(state: &State) -> Report {
+	if let Some((at, report)) = &*state.status.cached.lock().unwrap() {
+		if at.elapsed() < CACHE_TTL {
+			return report.clone();
+		}
+	}
+
+	let report = Report {
+		status: check(state).await,
+		updated_at: lock::now(),
+		incidents: state.status.incidents.lock().unwrap().clone(),
+	};
+	*state.status.cached.lock().unwrap() = Some((Instant::now(), report.clone()));
+
+	report
+}
+
+#[utoipa::path(
+	get,
+	path = "/status",
+	tag = "service",
+	responses(
+		(status = 200, description = "component health and open incidents", body = Report),
+		(status = 429, description = "polled too often", body = crate::error::ErrorBody),
+	),
+)]
+pub async fn status(
+	extract::State(state): extract::State<State>,
+	peer: Option<ConnectInfo<SocketAddr>>,
+	headers: HeaderMap,
+) -> Response {
+	let ip = honeypot::client_ip(
+		&headers,
+		peer.map(|ConnectInfo(peer)| peer),
+		&state.trusted_proxies,
+	);
+
+	if ip.is_some_and(|ip| !state.status.allow(ip)) {
+		return Error::RateLimited(RATE_WINDOW).into_response();
+	}
+
+	(
+		[(
+			header::CACHE_CONTROL,
+			HeaderValue::from_str(&format!("public, max-age={}", CACHE_TTL.as_secs())).unwrap(),
+		)],
+		state.json.reply(report(&state).await),
+	)
+		.into_response()
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct IncidentRequest {
+	pub message: String,
+}
+
+#[utoipa::path(
+	post,
+	path = "/admin/status/incidents",
+	tag = "admin",
+	request_body = IncidentRequest,
+	responses((status = 201, description = "the incident was opened", body = Incident)),
+	security(("admin_token" = [])),
+)]
+pub async fn open_incident(
+	extract::State(state): extract::State<State>,
+	Body(req): Body<IncidentRequest>,
+) -> (StatusCode, Reply<Incident>) {
+	let incident = state.status.open(req.message);
+	tracing::info!(id = incident.id, message = %incident.message, "incident opened");
+
+	(StatusCode::CREATED, state.json.reply(incident))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/admin/status/incidents/{id}",
+	tag = "admin",
+	params(("id" = u64, Path, description = "incident id")),
+	responses(
+		(status = 204, description = "the incident was resolved"),
+		(status = 410, description = "no such incident", body = crate::error::ErrorBody),
+	),
+	security(("admin_token" = [])),
+)]
+pub async fn resolve_incident(
+	extract::State(state): extract::State<State>,
+	Path(id): Path<u64>,
+) -> Result<StatusCode, Error> {
+	if state.status.resolve(id) {
+		tracing::info!(id, "incident resolved");
+
+		Ok(StatusCode::NO_CONTENT)
+	} else {
+		Err(Error::NotFound)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::net::Ipv6Addr;
+
+	use super::*;
+
+	#[test]
+	fn new_clients_are_refused_while_the_table_is_full() {
+		let board = Board::default();
+		let ip = |i: usize| IpAddr::V6(Ipv6Addr::from(i as u128));
+
+		for i in 0..MAX_CLIENTS {
+			assert!(board.allow(ip(i)));
+		}
+
+		assert!(!board.allow(ip(MAX_CLIENTS)));
+		assert!(board.allow(ip(0)));
+
+		board.prune();
+		assert_eq!(board.clients.len(), MAX_CLIENTS);
+	}
+}
@@ -0,0 +1,97 @@
+use axum::async_trait;
+
+use crate::{lock::Lock, quota, Error};
+
+pub mod deferred;
+pub mod memory;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub mod sql;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use deferred::DeferredStore;
+pub use memory::MemoryStore;
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStore;
+#[cfg(feature = "redis")]
+pub use redis::RedisStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[derive(Debug)]
+pub enum StoreError {
+	Full,
+	Backend(String),
+}
+
+impl From<StoreError> for Error {
+	fn from(e: StoreError) -> Self {
+		match e {
+			StoreError::Full => Error::InsufficientStorage,
+			StoreError::Backend(e) => {
+				tracing::error!(error = %e, "storage failure");
+
+				Error::Storage
+			}
+		}
+	}
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+impl From<sqlx::Error> for StoreError {
+	fn from(e: sqlx::Error) -> Self {
+		StoreError::Backend(e.to_string())
+	}
+}
+
+#[cfg(feature = "redis")]
+impl From<::redis::RedisError> for StoreError {
+	fn from(e: ::redis::RedisError) -> Self {
+		StoreError::Backend(e.to_string())
+	}
+}
+
+// everything handlers need from a backend; implementations must be safe to share between requests
+#[async_trait]
+pub trait LockStore: Send + Sync {
+	fn name(&self) -> &'static str;
+
+	// expired locks that have not been swept yet are treated as absent by get, list and remove
+	async fn get(&self, id: &str) -> Result<Option<Lock>, StoreError>;
+
+	async fn list(&self) -> Result<Vec<(String, Lock)>, StoreError>;
+
+	// inserts or replaces
+	async fn insert(&self, id: String, lock: Lock) -> Result<(), StoreError>;
+
+	// replaces an existing lock only; false when there is none
+	async fn update(&self, id: &str, lock: Lock) -> Result<bool, StoreError>;
+
+	async fn remove(&self, id: &str) -> Result<Option<Lock>, StoreError>;
+
+	// checks the token and atomically spends one use of a limited lock, dropping it once used up;
+	// returns the lock as left behind, or None when the id is unknown or the token is wrong
+	async fn consume(&self, id: &str, token: &str) -> Result<Option<Lock>, StoreError>;
+
+	async fn clear(&self) -> Result<(), StoreError>;
+
+	// drops locks whose lifetime ended by `now` and returns their ids
+	async fn remove_expired(&self, now: u64) -> Result<Vec<String>, StoreError>;
+
+	async fn usage(&self) -> Result<quota::Report, StoreError>;
+
+	// false when usage has to walk the whole backend, which is then left to explicit requests
+	fn usage_is_cheap(&self) -> bool {
+		true
+	}
+
+	// a cheap round trip to the backend, for readiness probes
+	async fn ping(&self) -> Result<(), StoreError>;
+
+	// called once on shutdown, after the last request has been answered
+	async fn close(&self) -> Result<(), StoreError>;
+}
@@ -0,0 +1,82 @@
+use std::sync::{Arc, OnceLock};
+
+use axum::async_trait;
+
+use super::{LockStore, StoreError};
+use crate::{lock::Lock, quota};
+
+// stands in for a store that is still being opened, so that the app can be built and serve its
+// health checks meanwhile; the startup gate keeps every other request away until it is set
+#[derive(Default)]
+pub struct DeferredStore {
+	store: OnceLock<Arc<dyn LockStore>>,
+}
+
+impl DeferredStore {
+	// only the first store set is kept
+	pub fn set(&self, store: Arc<dyn LockStore>) {
+		let _ = self.store.set(store);
+	}
+
+	fn store(&self) -> Result<&dyn LockStore, StoreError> {
+		self.store
+			.get()
+			.map(|store| &**store)
+			.ok_or_else(|| StoreError::Backend("the store is not open yet".to_string()))
+	}
+}
+
+#[async_trait]
+impl LockStore for DeferredStore {
+	fn name(&self) -> &'static str {
+		self.store().map_or("starting", |store| store.name())
+	}
+
+	async fn get(&self, id: &str) -> Result<Option<Lock>, StoreError> {
+		self.store()?.get(id).await
+	}
+
+	async fn list(&self) -> Result<Vec<(String, Lock)>, StoreError> {
+		self.store()?.list().await
+	}
+
+	async fn insert(&self, id: String, lock: Lock) -> Result<(), StoreError> {
+		self.store()?.insert(id, lock).await
+	}
+
+	async fn update(&self, id: &str, lock: Lock) -> Result<bool, StoreError> {
+		self.store()?.update(id, lock).await
+	}
+
+	async fn remove(&self, id: &str) -> Result<Option<Lock>, StoreError> {
+		self.store()?.remove(id).await
+	}
+
+	async fn consume(&self, id: &str, token: &str) -> Result<Option<Lock>, StoreError> {
+		self.store()?.consume(id, token).await
+	}
+
+	async fn clear(&self) -> Result<(), StoreError> {
+		self.store()?.clear().await
+	}
+
+	async fn remove_expired(&self, now: u64) -> Result<Vec<String>, StoreError> {
+		self.store()?.remove_expired(now).await
+	}
+
+	async fn usage(&self) -> Result<quota::Report, StoreError> {
+		self.store()?.usage().await
+	}
+
+	fn usage_is_cheap(&self) -> bool {
+		self.store().is_ok_and(|store| store.usage_is_cheap())
+	}
+
+	async fn ping(&self) -> Result<(), StoreError> {
+		self.store()?.ping().await
+	}
+
+	async fn close(&self) -> Result<(), StoreError> {
+		self.store()?.close().await
+	}
+}
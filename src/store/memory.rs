@@ -0,0 +1,480 @@
+use std::{
+	path::PathBuf,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+};
+
+use axum::async_trait;
+use dashmap::{
+	mapref::entry::{Entry, OccupiedEntry},
+	DashMap,
+};
+use tokio::sync::Mutex;
+
+use super::{LockStore, StoreError};
+use crate::{
+	lock::{self, Lock},
+	quota::{self, Limits, Usage},
+	snapshot::{self, Snapshot},
+};
+
+// a concurrent map, optionally mirrored to a snapshot file before every mutation is answered
+pub struct MemoryStore {
+	locks: Arc<DashMap<String, Lock>>,
+	limits: Limits,
+	usage: Usage,
+	file: Option<DataFile>,
+}
+
+// mutations are numbered as they are applied; those that pile up while a snapshot is being written
+// all go out together in the next one rather than each rewriting the file
+struct DataFile {
+	path: PathBuf,
+	changes: AtomicU64,
+	// the last change known to be on disk; held while writing, so writes happen one at a time
+	saved: Mutex<u64>,
+}
+
+impl MemoryStore {
+	pub fn new(locks: Arc<DashMap<String, Lock>>) -> Self {
+		let usage = Usage::default();
+		// limits are not applied to existing data, only to what gets inserted later
+		for entry in locks.iter() {
+			usage.record(1, quota::footprint(entry.key(), entry.value()));
+		}
+
+		Self {
+			locks,
+			limits: Limits::default(),
+			usage,
+			file: None,
+		}
+	}
+
+	pub fn load(path: PathBuf) -> Result<Self, snapshot::Error> {
+		let snapshot = Snapshot::load(&path)?;
+		let mut store = Self::new(Arc::new(snapshot.locks.into_iter().collect()));
+		store.file = Some(DataFile {
+			path,
+			changes: AtomicU64::new(0),
+			saved: Mutex::new(0),
+		});
+
+		Ok(store)
+	}
+
+	pub fn with_limits(mut self, limits: Limits) -> Self {
+		self.limits = limits;
+
+		self
+	}
+
+	pub fn snapshot(&self) -> Snapshot {
+		Snapshot::new(
+			self.locks
+				.iter()
+				.map(|entry| (entry.key().clone(), entry.value().clone()))
+				.collect(),
+		)
+	}
+
+	fn replace(
+		&self,
+		mut entry: OccupiedEntry<'_, String, Lock>,
+		lock: Lock,
+	) -> Result<(), StoreError> {
+		let size = quota::footprint(entry.key(), &lock);
+		let old = quota::footprint(entry.key(), entry.get());
+
+		if size > old && !self.usage.try_reserve(&self.limits, 0, size - old) {
+			return Err(StoreError::Full);
+		}
+
+		self.usage.release(0, old.saturating_sub(size));
+		entry.insert(lock);
+
+		Ok(())
+	}
+
+	// puts back what a mutation replaced, unless the entry has changed again since
+	fn restore(&self, id: String, current: Option<&Lock>, previous: Option<Lock>) {
+		match self.locks.entry(id) {
+			Entry::Occupied(mut entry) if Some(entry.get()) == current => {
+				self.usage
+					.release(1, quota::footprint(entry.key(), entry.get()));
+
+				match previous {
+					Some(previous) => {
+						self.usage
+							.record(1, quota::footprint(entry.key(), &previous));
+						entry.insert(previous);
+					}
+					None => {
+						entry.remove();
+					}
+				}
+			}
+			Entry::Vacant(entry) if current.is_none() => {
+				if let Some(previous) = previous {
+					self.usage
+						.record(1, quota::footprint(entry.key(), &previous));
+					entry.insert(previous);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	// writes the change just applied, along with any other waiting to be written; when that fails
+	// `undo` reverts it before the next write is attempted, so that memory and disk agree
+	async fn persist(&self, undo: impl FnOnce() + Send) -> Result<(), StoreError> {
+		let Some(file) = &self.file else {
+			return Ok(());
+		};
+		let change = file.changes.fetch_add(1, Ordering::SeqCst) + 1;
+		let mut saved = file.saved.lock().await;
+
+		if *saved >= change {
+			return Ok(());
+		}
+
+		let upto = file.changes.load(Ordering::SeqCst);
+		let snapshot = self.snapshot();
+		let target = file.path.clone();
+		let result = tokio::task::spawn_blocking(move || snapshot.save(&target))
+			.await
+			.map_err(|e| StoreError::Backend(e.to_string()))
+			.and_then(|result| {
+				result
+					.map_err(|e| StoreError::Backend(format!("failed to persist snapshot: {}", e)))
+			});
+
+		match result {
+			Ok(()) => *saved = upto,
+			Err(_) => undo(),
+		}
+
+		result
+	}
+}
+
+#[async_trait]
+impl LockStore for MemoryStore {
+	fn name(&self) -> &'static str {
+		if self.file.is_some() {
+			"file"
+		} else {
+			"memory"
+		}
+	}
+
+	async fn get(&self, id: &str) -> Result<Option<Lock>, StoreError> {
+		let now = lock::now();
+
+		Ok(self
+			.locks
+			.get(id)
+			.filter(|lock| !lock.is_expired(now))
+			.map(|lock| lock.clone()))
+	}
+
+	async fn list(&self) -> Result<Vec<(String, Lock)>, StoreError> {
+		let now = lock::now();
+
+		Ok(self
+			.locks
+			.iter()
+			.filter(|entry| !entry.value().is_expired(now))
+			.map(|entry| (entry.key().clone(), entry.value().clone()))
+			.collect())
+	}
+
+	// rejects rather than evicts when full: dropping someone's lock to make room would silently unlock it
+	async fn insert(&self, id: String, lock: Lock) -> Result<(), StoreError> {
+		let previous = match self.locks.entry(id.clone()) {
+			Entry::Occupied(entry) => {
+				let previous = entry.get().clone();
+				self.replace(entry, lock.clone())?;
+
+				Some(previous)
+			}
+			Entry::Vacant(entry) => {
+				if !self
+					.usage
+					.try_reserve(&self.limits, 1, quota::footprint(entry.key(), &lock))
+				{
+					return Err(StoreError::Full);
+				}
+
+				entry.insert(lock.clone());
+
+				None
+			}
+		};
+
+		self.persist(|| self.restore(id, Some(&lock), previous))
+			.await
+	}
+
+	async fn update(&self, id: &str, lock: Lock) -> Result<bool, StoreError> {
+		let previous = match self.locks.entry(id.to_string()) {
+			Entry::Occupied(entry) => {
+				let previous = entry.get().clone();
+				self.replace(entry, lock.clone())?;
+
+				previous
+			}
+			Entry::Vacant(_) => return Ok(false),
+		};
+
+		self.persist(|| self.restore(id.to_string(), Some(&lock), Some(previous)))
+			.await?;
+
+		Ok(true)
+	}
+
+	async fn remove(&self, id: &str) -> Result<Option<Lock>, StoreError> {
+		let Some((id, lock)) = self.locks.remove(id) else {
+			return Ok(None);
+		};
+		self.usage.release(1, quota::footprint(&id, &lock));
+		self.persist(|| self.restore(id, None, Some(lock.clone())))
+			.await?;
+
+		Ok(Some(lock).filter(|lock| !lock.is_expired(lock::now())))
+	}
+
+	async fn consume(&self, id: &str, token: &str) -> Result<Option<Lock>, StoreError> {
+		let Entry::Occupied(mut entry) = self.locks.entry(id.to_string()) else {
+			return Ok(None);
+		};
+		let lock = entry.get_mut();
+
+		if lock.is_expired(lock::now()) || !lock.verify(token) {
+			return Ok(None);
+		}
+
+		let Some(uses) = lock.uses_left else {
+			return Ok(Some(lock.clone()));
+		};
+		let previous = lock.clone();
+		lock.uses_left = Some(uses.saturating_sub(1));
+		let lock = lock.clone();
+		let left = if lock.uses_left == Some(0) {
+			let (id, lock) = entry.remove_entry();
+			self.usage.release(1, quota::footprint(&id, &lock));
+
+			None
+		} else {
+			drop(entry);
+
+			Some(&lock)
+		};
+
+		self.persist(|| self.restore(id.to_string(), left, Some(previous)))
+			.await?;
+
+		Ok(Some(lock))
+	}
+
+	async fn clear(&self) -> Result<(), StoreError> {
+		let mut removed = Vec::new();
+
+		self.locks.retain(|id, lock| {
+			self.usage.release(1, quota::footprint(id, lock));
+			removed.push((id.clone(), lock.clone()));
+
+			false
+		});
+
+		self.persist(|| {
+			for (id, lock) in removed {
+				self.restore(id, None, Some(lock));
+			}
+		})
+		.await
+	}
+
+	async fn remove_expired(&self, now: u64) -> Result<Vec<String>, StoreError> {
+		let mut expired = Vec::new();
+
+		self.locks.retain(|id, lock| {
+			if !lock.is_expired(now) {
+				return true;
+			}
+
+			self.usage.release(1, quota::footprint(id, lock));
+			expired.push((id.clone(), lock.clone()));
+
+			false
+		});
+
+		if !expired.is_empty() {
+			// put back to be swept again, as nothing has been removed from disk
+			self.persist(|| {
+				for (id, lock) in expired.iter().cloned() {
+					self.restore(id, None, Some(lock));
+				}
+			})
+			.await?;
+		}
+
+		Ok(expired.into_iter().map(|(id, _)| id).collect())
+	}
+
+	async fn usage(&self) -> Result<quota::Report, StoreError> {
+		Ok(self.usage.report(&self.limits))
+	}
+
+	async fn ping(&self) -> Result<(), StoreError> {
+		Ok(())
+	}
+
+	// every mutation is already on disk; this only guards against a write that failed earlier
+	async fn close(&self) -> Result<(), StoreError> {
+		self.persist(|| {}).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{env, fs, process};
+
+	use super::*;
+
+	fn data_file(name: &str) -> PathBuf {
+		let dir = env::temp_dir().join(format!("touchid-{}-{}", name, process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+
+		dir.join("locks")
+	}
+
+	fn lock(token: &str) -> Lock {
+		Lock::new(token.to_string(), None)
+	}
+
+	#[tokio::test]
+	async fn failed_writes_are_rolled_back() {
+		let path = data_file("rollback");
+		let store = MemoryStore::load(path.clone()).unwrap();
+		store.insert("a".to_string(), lock("one")).await.unwrap();
+
+		// the temporary file cannot be created where a directory is in the way
+		let blocker = path.with_file_name("locks.tmp");
+		fs::create_dir(&blocker).unwrap();
+
+		assert!(store.insert("b".to_string(), lock("two")).await.is_err());
+		assert!(store.insert("a".to_string(), lock("three")).await.is_err());
+		assert!(store.remove("a").await.is_err());
+		assert!(store.clear().await.is_err());
+
+		assert_eq!(store.get("a").await.unwrap(), Some(lock("one")));
+		assert_eq!(store.get("b").await.unwrap(), None);
+		assert_eq!(store.usage().await.unwrap().entries, 1);
+
+		fs::remove_dir(&blocker).unwrap();
+		store.close().await.unwrap();
+
+		let reloaded = MemoryStore::load(path.clone()).unwrap();
+		assert_eq!(
+			reloaded.list().await.unwrap(),
+			vec![("a".to_string(), lock("one"))]
+		);
+
+		fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+	async fn concurrent_consumes_never_overspend() {
+		let path = data_file("consume");
+		let stores = [
+			MemoryStore::new(Default::default()),
+			MemoryStore::load(path.clone()).unwrap(),
+		];
+
+		for store in stores {
+			let store = Arc::new(store);
+			store
+				.insert("door".to_string(), lock("tok").with_max_uses(Some(10)))
+				.await
+				.unwrap();
+
+			let attempts: Vec<_> = (0..64)
+				.map(|_| {
+					let store = store.clone();
+
+					tokio::spawn(async move { store.consume("door", "tok").await.unwrap() })
+				})
+				.collect();
+			let mut left = Vec::new();
+
+			for attempt in attempts {
+				if let Some(lock) = attempt.await.unwrap() {
+					left.push(lock.uses_left.unwrap());
+				}
+			}
+
+			left.sort_unstable();
+			assert_eq!(left, (0..10).collect::<Vec<_>>());
+			assert_eq!(store.get("door").await.unwrap(), None);
+			assert_eq!(store.usage().await.unwrap().entries, 0);
+		}
+
+		assert!(MemoryStore::load(path.clone())
+			.unwrap()
+			.list()
+			.await
+			.unwrap()
+			.is_empty());
+
+		fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[tokio::test]
+	async fn usage_is_accounted_and_limits_push_back() {
+		let id = |n: &str| n.to_string();
+		let size = |id: &str, token: &str| quota::footprint(id, &lock(token));
+		let store = MemoryStore::new(Default::default()).with_limits(Limits {
+			max_entries: Some(2),
+			max_bytes: Some(size("a", "one") + size("b", "two") + 4),
+		});
+		let usage = |store: &MemoryStore| {
+			let report = store.usage.report(&store.limits);
+
+			(report.entries, report.bytes)
+		};
+
+		store.insert(id("a"), lock("one")).await.unwrap();
+		store.insert(id("b"), lock("two")).await.unwrap();
+		assert_eq!(usage(&store), (2, size("a", "one") + size("b", "two")));
+
+		// a third lock is refused, and answered with 507
+		let full = store.insert(id("c"), lock("three")).await.unwrap_err();
+		assert!(matches!(full, StoreError::Full));
+		assert_eq!(
+			crate::Error::from(full).status(),
+			axum::http::StatusCode::INSUFFICIENT_STORAGE
+		);
+		assert_eq!(usage(&store).0, 2);
+
+		// replacing is charged the difference, within the byte limit or not at all
+		store.insert(id("a"), lock("four")).await.unwrap();
+		assert_eq!(usage(&store), (2, size("a", "four") + size("b", "two")));
+		assert!(matches!(
+			store.insert(id("a"), lock("far too long")).await,
+			Err(StoreError::Full)
+		));
+		assert_eq!(store.get("a").await.unwrap(), Some(lock("four")));
+		store.insert(id("a"), lock("1")).await.unwrap();
+		assert_eq!(usage(&store), (2, size("a", "1") + size("b", "two")));
+
+		// removing gives the room back
+		store.remove("b").await.unwrap();
+		assert_eq!(usage(&store), (1, size("a", "1")));
+		store.insert(id("c"), lock("three")).await.unwrap();
+		assert_eq!(usage(&store), (2, size("a", "1") + size("c", "three")));
+	}
+}
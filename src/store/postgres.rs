@@ -0,0 +1,25 @@
+use std::str::FromStr;
+
+use sqlx::{
+	postgres::{PgConnectOptions, PgPoolOptions},
+	Postgres,
+};
+
+use super::sql::SqlStore;
+
+pub type PostgresStore = SqlStore<Postgres>;
+
+impl PostgresStore {
+	// brings the schema up to date; the database itself has to exist
+	pub async fn connect(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+		let options = PgConnectOptions::from_str(url)?;
+		let pool = PgPoolOptions::new()
+			.max_connections(max_connections)
+			.connect_with(options)
+			.await?;
+
+		sqlx::migrate!("migrations/postgres").run(&pool).await?;
+
+		Ok(Self::new(pool, "postgres"))
+	}
+}
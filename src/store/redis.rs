@@ -0,0 +1,227 @@
+use axum::async_trait;
+use redis::{aio::ConnectionManager, AsyncCommands};
+
+use super::{LockStore, StoreError};
+use crate::{lock::Lock, quota};
+
+// spends one use of the lock at KEYS[1] unless its token changed from ARGV[1], keeping its expiry.
+// a lock replaced by an unlimited one meanwhile has no uses_left, or a json null for it
+const CONSUME: &str = r#"
+local value = redis.call('GET', KEYS[1])
+if not value then return nil end
+local lock = cjson.decode(value)
+if lock.token ~= ARGV[1] or type(lock.uses_left) ~= 'number' or lock.uses_left < 1 then return nil end
+lock.uses_left = lock.uses_left - 1
+value = cjson.encode(lock)
+if lock.uses_left == 0 then
+	redis.call('DEL', KEYS[1])
+else
+	redis.call('SET', KEYS[1], value, 'KEEPTTL')
+end
+return value
+"#;
+
+// shares locks between instances; every lock is a json value under `{prefix}{id}`, and only keys
+// under the prefix are ever listed or cleared
+pub struct RedisStore {
+	conn: ConnectionManager,
+	prefix: String,
+}
+
+fn decode(value: &str) -> Result<Lock, StoreError> {
+	serde_json::from_str(value).map_err(|e| StoreError::Backend(e.to_string()))
+}
+
+fn encode(lock: &Lock) -> Result<String, StoreError> {
+	serde_json::to_string(lock).map_err(|e| StoreError::Backend(e.to_string()))
+}
+
+// the prefix as a SCAN pattern that matches nothing but keys starting with it
+fn pattern(prefix: &str) -> String {
+	let mut pattern = String::with_capacity(prefix.len() + 1);
+
+	for c in prefix.chars() {
+		if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+			pattern.push('\\');
+		}
+
+		pattern.push(c);
+	}
+
+	pattern.push('*');
+
+	pattern
+}
+
+impl RedisStore {
+	// reconnects on its own when the connection drops
+	pub async fn connect(url: &str, prefix: String) -> Result<Self, redis::RedisError> {
+		let conn = ConnectionManager::new(redis::Client::open(url)?).await?;
+
+		Ok(Self { conn, prefix })
+	}
+
+	fn key(&self, id: &str) -> String {
+		format!("{}{}", self.prefix, id)
+	}
+
+	// expiring locks are handed to redis, which drops them at `expires_at` on its own
+	fn set(&self, lock: &Lock, id: &str) -> Result<redis::Cmd, StoreError> {
+		let mut cmd = redis::cmd("SET");
+		cmd.arg(self.key(id)).arg(encode(lock)?);
+
+		if let Some(at) = lock.expires_at {
+			let at = i64::try_from(at)
+				.map_err(|e| StoreError::Backend(format!("expiry out of range: {}", e)))?;
+			cmd.arg("EXAT").arg(at);
+		}
+
+		Ok(cmd)
+	}
+
+	async fn keys(&self) -> Result<Vec<String>, StoreError> {
+		let mut conn = self.conn.clone();
+		let mut iter = conn.scan_match::<_, String>(pattern(&self.prefix)).await?;
+		let mut keys = Vec::new();
+
+		while let Some(key) = iter.next_item().await {
+			keys.push(key);
+		}
+
+		Ok(keys)
+	}
+}
+
+#[async_trait]
+impl LockStore for RedisStore {
+	fn name(&self) -> &'static str {
+		"redis"
+	}
+
+	async fn get(&self, id: &str) -> Result<Option<Lock>, StoreError> {
+		let value: Option<String> = self.conn.clone().get(self.key(id)).await?;
+
+		value.as_deref().map(decode).transpose()
+	}
+
+	async fn list(&self) -> Result<Vec<(String, Lock)>, StoreError> {
+		let keys = self.keys().await?;
+
+		if keys.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		let values: Vec<Option<String>> = self.conn.clone().mget(&keys).await?;
+		let mut locks = Vec::with_capacity(keys.len());
+
+		// keys removed between the scan and the read come back as nil
+		for (key, value) in keys.into_iter().zip(values) {
+			if let Some(value) = value {
+				locks.push((key[self.prefix.len()..].to_string(), decode(&value)?));
+			}
+		}
+
+		locks.sort_by(|a, b| a.0.cmp(&b.0));
+
+		Ok(locks)
+	}
+
+	async fn insert(&self, id: String, lock: Lock) -> Result<(), StoreError> {
+		self.set(&lock, &id)?
+			.query_async::<()>(&mut self.conn.clone())
+			.await?;
+
+		Ok(())
+	}
+
+	async fn update(&self, id: &str, lock: Lock) -> Result<bool, StoreError> {
+		let replaced: Option<String> = self
+			.set(&lock, id)?
+			.arg("XX")
+			.query_async(&mut self.conn.clone())
+			.await?;
+
+		Ok(replaced.is_some())
+	}
+
+	async fn remove(&self, id: &str) -> Result<Option<Lock>, StoreError> {
+		let value: Option<String> = self.conn.clone().get_del(self.key(id)).await?;
+
+		value.as_deref().map(decode).transpose()
+	}
+
+	async fn consume(&self, id: &str, token: &str) -> Result<Option<Lock>, StoreError> {
+		let Some(lock) = self.get(id).await?.filter(|lock| lock.verify(token)) else {
+			return Ok(None);
+		};
+
+		if lock.uses_left.is_none() {
+			return Ok(Some(lock));
+		}
+
+		let value: Option<String> = redis::Script::new(CONSUME)
+			.key(self.key(id))
+			.arg(token)
+			.invoke_async(&mut self.conn.clone())
+			.await?;
+
+		value.as_deref().map(decode).transpose()
+	}
+
+	async fn clear(&self) -> Result<(), StoreError> {
+		let keys = self.keys().await?;
+
+		if !keys.is_empty() {
+			self.conn.clone().unlink::<_, ()>(keys).await?;
+		}
+
+		Ok(())
+	}
+
+	// nothing to sweep: redis expires keys itself, though pollers of /locks/:id/state are not told
+	async fn remove_expired(&self, _now: u64) -> Result<Vec<String>, StoreError> {
+		Ok(Vec::new())
+	}
+
+	// walks the whole keyspace; meant for the occasional /usage call, not hot paths
+	async fn usage(&self) -> Result<quota::Report, StoreError> {
+		let locks = self.list().await?;
+
+		Ok(quota::Report {
+			entries: locks.len(),
+			bytes: locks
+				.iter()
+				.map(|(id, lock)| id.len() + lock.token.len())
+				.sum(),
+			max_entries: None,
+			max_bytes: None,
+		})
+	}
+
+	fn usage_is_cheap(&self) -> bool {
+		false
+	}
+
+	async fn ping(&self) -> Result<(), StoreError> {
+		let mut conn = self.conn.clone();
+		redis::cmd("PING").query_async::<()>(&mut conn).await?;
+
+		Ok(())
+	}
+
+	// every write is acknowledged by redis before it is answered; nothing is buffered here
+	async fn close(&self) -> Result<(), StoreError> {
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn the_scan_pattern_only_matches_the_prefix() {
+		assert_eq!(pattern("touchid:lock:"), "touchid:lock:*");
+		assert_eq!(pattern("a*b[1]?\\:"), "a\\*b\\[1\\]\\?\\\\:*");
+	}
+}
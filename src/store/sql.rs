@@ -0,0 +1,240 @@
+use axum::async_trait;
+use sqlx::{ColumnIndex, Database, Decode, Encode, Executor, IntoArguments, Pool, Type};
+
+use super::{LockStore, StoreError};
+use crate::{
+	lock::{self, Lock},
+	quota,
+};
+
+// the schema and queries shared by the sqlite and postgres stores, which both take $n placeholders;
+// each backend only adds how it connects
+pub struct SqlStore<DB: Database> {
+	pool: Pool<DB>,
+	name: &'static str,
+}
+
+impl<DB: Database> SqlStore<DB> {
+	pub(super) fn new(pool: Pool<DB>, name: &'static str) -> Self {
+		Self { pool, name }
+	}
+}
+
+type Row = (String, Option<i64>, Option<i64>);
+
+fn to_lock((token, expires_at, uses_left): Row) -> Lock {
+	Lock {
+		token,
+		expires_at: expires_at.map(|at| at as u64),
+		uses_left: uses_left.map(|uses| uses as u32),
+	}
+}
+
+// refused rather than wrapped, which would store a lock that is expired from the start
+fn expires_at(at: Option<u64>) -> Result<Option<i64>, StoreError> {
+	at.map(i64::try_from)
+		.transpose()
+		.map_err(|e| StoreError::Backend(format!("expiry out of range: {}", e)))
+}
+
+fn now() -> i64 {
+	lock::now() as i64
+}
+
+#[async_trait]
+impl<DB> LockStore for SqlStore<DB>
+where
+	DB: Database,
+	for<'c> &'c Pool<DB>: Executor<'c, Database = DB>,
+	for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+	for<'q> String: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+	for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+	for<'q> i64: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+	for<'q> Option<i64>: Encode<'q, DB>,
+	usize: ColumnIndex<DB::Row>,
+{
+	fn name(&self) -> &'static str {
+		self.name
+	}
+
+	async fn get(&self, id: &str) -> Result<Option<Lock>, StoreError> {
+		let row: Option<Row> = sqlx::query_as(
+			"SELECT token, expires_at, uses_left FROM locks \
+			 WHERE id = $1 AND (expires_at IS NULL OR expires_at > $2)",
+		)
+		.bind(id)
+		.bind(now())
+		.fetch_optional(&self.pool)
+		.await?;
+
+		Ok(row.map(to_lock))
+	}
+
+	async fn list(&self) -> Result<Vec<(String, Lock)>, StoreError> {
+		let rows: Vec<(String, String, Option<i64>, Option<i64>)> = sqlx::query_as(
+			"SELECT id, token, expires_at, uses_left FROM locks \
+			 WHERE expires_at IS NULL OR expires_at > $1 ORDER BY id",
+		)
+		.bind(now())
+		.fetch_all(&self.pool)
+		.await?;
+
+		Ok(rows
+			.into_iter()
+			.map(|(id, token, expires_at, uses_left)| (id, to_lock((token, expires_at, uses_left))))
+			.collect())
+	}
+
+	async fn insert(&self, id: String, lock: Lock) -> Result<(), StoreError> {
+		sqlx::query(
+			"INSERT INTO locks (id, token, expires_at, uses_left) VALUES ($1, $2, $3, $4) \
+			 ON CONFLICT (id) DO UPDATE \
+			 SET token = excluded.token, expires_at = excluded.expires_at, uses_left = excluded.uses_left",
+		)
+		.bind(id)
+		.bind(lock.token)
+		.bind(expires_at(lock.expires_at)?)
+		.bind(lock.uses_left.map(i64::from))
+		.execute(&self.pool)
+		.await?;
+
+		Ok(())
+	}
+
+	async fn update(&self, id: &str, lock: Lock) -> Result<bool, StoreError> {
+		let updated: Option<String> = sqlx::query_scalar(
+			"UPDATE locks SET token = $1, expires_at = $2, uses_left = $3 WHERE id = $4 RETURNING id",
+		)
+		.bind(lock.token)
+		.bind(expires_at(lock.expires_at)?)
+		.bind(lock.uses_left.map(i64::from))
+		.bind(id)
+		.fetch_optional(&self.pool)
+		.await?;
+
+		Ok(updated.is_some())
+	}
+
+	async fn remove(&self, id: &str) -> Result<Option<Lock>, StoreError> {
+		let row: Option<Row> = sqlx::query_as(
+			"DELETE FROM locks WHERE id = $1 RETURNING token, expires_at, uses_left",
+		)
+		.bind(id)
+		.fetch_optional(&self.pool)
+		.await?;
+
+		Ok(row
+			.map(to_lock)
+			.filter(|lock| !lock.is_expired(lock::now())))
+	}
+
+	async fn consume(&self, id: &str, token: &str) -> Result<Option<Lock>, StoreError> {
+		let Some(lock) = self.get(id).await?.filter(|lock| lock.verify(token)) else {
+			return Ok(None);
+		};
+
+		if lock.uses_left.is_none() {
+			return Ok(Some(lock));
+		}
+
+		// the token is matched again so that a lock replaced in the meantime is left alone
+		let row: Option<Row> = sqlx::query_as(
+			"UPDATE locks SET uses_left = uses_left - 1 \
+			 WHERE id = $1 AND token = $2 AND uses_left > 0 \
+			 RETURNING token, expires_at, uses_left",
+		)
+		.bind(id)
+		.bind(token)
+		.fetch_optional(&self.pool)
+		.await?;
+		let lock = row.map(to_lock);
+
+		if lock.as_ref().is_some_and(|lock| lock.uses_left == Some(0)) {
+			sqlx::query("DELETE FROM locks WHERE id = $1 AND uses_left = 0")
+				.bind(id)
+				.execute(&self.pool)
+				.await?;
+		}
+
+		Ok(lock)
+	}
+
+	async fn clear(&self) -> Result<(), StoreError> {
+		sqlx::query("DELETE FROM locks").execute(&self.pool).await?;
+
+		Ok(())
+	}
+
+	async fn remove_expired(&self, now: u64) -> Result<Vec<String>, StoreError> {
+		Ok(
+			sqlx::query_scalar("DELETE FROM locks WHERE expires_at <= $1 RETURNING id")
+				.bind(now as i64)
+				.fetch_all(&self.pool)
+				.await?,
+		)
+	}
+
+	// limits are only enforced by the in-memory store; the database is bounded by its disk
+	async fn usage(&self) -> Result<quota::Report, StoreError> {
+		let (entries, bytes): (i64, i64) = sqlx::query_as(
+			"SELECT COUNT(*), COALESCE(SUM(LENGTH(id) + LENGTH(token)), 0) FROM locks",
+		)
+		.fetch_one(&self.pool)
+		.await?;
+
+		Ok(quota::Report {
+			entries: entries as usize,
+			bytes: bytes as usize,
+			max_entries: None,
+			max_bytes: None,
+		})
+	}
+
+	async fn ping(&self) -> Result<(), StoreError> {
+		sqlx::query("SELECT 1").execute(&self.pool).await?;
+
+		Ok(())
+	}
+
+	async fn close(&self) -> Result<(), StoreError> {
+		self.pool.close().await;
+
+		Ok(())
+	}
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+	use super::*;
+	use crate::store::SqliteStore;
+
+	#[tokio::test]
+	async fn shared_queries_run_on_sqlite() {
+		// a single connection, as every in-memory connection opens a database of its own
+		let store = SqliteStore::connect("sqlite::memory:", 1).await.unwrap();
+		let lock = Lock::new("tok".to_string(), None).with_max_uses(Some(2));
+
+		assert!(!store.update("door", lock.clone()).await.unwrap());
+		store
+			.insert("door".to_string(), lock.clone())
+			.await
+			.unwrap();
+		assert!(store.update("door", lock).await.unwrap());
+		assert_eq!(store.usage().await.unwrap().entries, 1);
+
+		assert!(store.consume("door", "nope").await.unwrap().is_none());
+		let left = store.consume("door", "tok").await.unwrap().unwrap();
+		assert_eq!(left.uses_left, Some(1));
+		let left = store.consume("door", "tok").await.unwrap().unwrap();
+		assert_eq!(left.uses_left, Some(0));
+		assert!(store.get("door").await.unwrap().is_none());
+
+		store
+			.insert("gate".to_string(), Lock::new("tok".to_string(), None))
+			.await
+			.unwrap();
+		assert_eq!(store.list().await.unwrap().len(), 1);
+		assert!(store.remove("gate").await.unwrap().is_some());
+		assert!(store.remove("gate").await.unwrap().is_none());
+	}
+}
@@ -0,0 +1,25 @@
+use std::str::FromStr;
+
+use sqlx::{
+	sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+	Sqlite,
+};
+
+use super::sql::SqlStore;
+
+pub type SqliteStore = SqlStore<Sqlite>;
+
+impl SqliteStore {
+	// creates the database file if needed and brings the schema up to date
+	pub async fn connect(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+		let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+		let pool = SqlitePoolOptions::new()
+			.max_connections(max_connections)
+			.connect_with(options)
+			.await?;
+
+		sqlx::migrate!("migrations/sqlite").run(&pool).await?;
+
+		Ok(Self::new(pool, "sqlite"))
+	}
+}
@@ -0,0 +1,105 @@
+use std::{
+	env, io,
+	net::TcpListener,
+	os::unix::{io::FromRawFd, net::UnixDatagram},
+	process,
+	time::Duration,
+};
+
+// see sd_listen_fds(3)
+const SD_LISTEN_FDS_START: i32 = 3;
+
+fn for_this_process(var: &str) -> bool {
+	env::var(var)
+		.ok()
+		.and_then(|pid| pid.parse::<u32>().ok())
+		.is_some_and(|pid| pid == process::id())
+}
+
+// returns the first socket passed by systemd socket activation, if any. it clears the variables
+// describing it, so it must be called before any other thread is started
+pub fn listener() -> io::Result<Option<TcpListener>> {
+	if !for_this_process("LISTEN_PID") {
+		return Ok(None);
+	}
+
+	let fds = env::var("LISTEN_FDS")
+		.ok()
+		.and_then(|fds| fds.parse::<i32>().ok())
+		.unwrap_or(0);
+
+	env::remove_var("LISTEN_PID");
+	env::remove_var("LISTEN_FDS");
+	env::remove_var("LISTEN_FDNAMES");
+
+	if fds < 1 {
+		return Ok(None);
+	}
+
+	// safety: systemd hands over ownership of the fds starting at SD_LISTEN_FDS_START
+	let listener = unsafe { TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
+	listener.set_nonblocking(true)?;
+
+	Ok(Some(listener))
+}
+
+// sends a state string (e.g. "READY=1") to the service manager; a no-op outside of systemd
+pub fn notify(state: &str) -> io::Result<()> {
+	let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+		return Ok(());
+	};
+	let socket = UnixDatagram::unbound()?;
+	let path = path.to_string_lossy();
+
+	if let Some(name) = path.strip_prefix('@') {
+		send_abstract(&socket, name, state)?;
+	} else {
+		socket.send_to(state.as_bytes(), path.as_ref())?;
+	}
+
+	Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn send_abstract(socket: &UnixDatagram, name: &str, state: &str) -> io::Result<()> {
+	use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+	let addr = SocketAddr::from_abstract_name(name)?;
+	socket.send_to_addr(state.as_bytes(), &addr)?;
+
+	Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send_abstract(_: &UnixDatagram, _: &str, _: &str) -> io::Result<()> {
+	Err(io::Error::new(
+		io::ErrorKind::Unsupported,
+		"abstract notify sockets are linux only",
+	))
+}
+
+// how often to ping the watchdog: half of WatchdogSec as recommended by sd_watchdog_enabled(3)
+pub fn watchdog_interval() -> Option<Duration> {
+	if env::var_os("WATCHDOG_PID").is_some() && !for_this_process("WATCHDOG_PID") {
+		return None;
+	}
+
+	env::var("WATCHDOG_USEC")
+		.ok()
+		.and_then(|usec| usec.parse::<u64>().ok())
+		.filter(|usec| *usec > 0)
+		.map(|usec| Duration::from_micros(usec / 2))
+}
+
+pub fn spawn_watchdog() {
+	if let Some(interval) = watchdog_interval() {
+		tokio::spawn(async move {
+			let mut ticker = tokio::time::interval(interval);
+
+			loop {
+				ticker.tick().await;
+				let _ = notify("WATCHDOG=1");
+			}
+		});
+	}
+}
@@ -0,0 +1,87 @@
+use std::{
+	future::Future,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant},
+};
+
+use axum::{extract, http::Request, middleware::Next, response::Response};
+use tracing::Instrument;
+
+use crate::State;
+
+tokio::task_local! {
+	static TIMINGS: Arc<Timings>;
+}
+
+// per-request time spent in each phase, in microseconds
+#[derive(Default, Debug)]
+pub struct Timings {
+	handler: AtomicU64,
+	storage: AtomicU64,
+}
+
+fn add(counter: &AtomicU64, elapsed: Duration) {
+	counter.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+}
+
+fn millis(micros: u64) -> f64 {
+	micros as f64 / 1000.0
+}
+
+// attributes the time spent in `fut` to storage for the current request, if any, and runs it
+// in a child span of the request named after the operation
+pub async fn storage<F: Future>(op: &'static str, fut: F) -> F::Output {
+	let started = Instant::now();
+	let out = fut.instrument(tracing::info_span!("store", op)).await;
+	let _ = TIMINGS.try_with(|timings| add(&timings.storage, started.elapsed()));
+
+	out
+}
+
+// innermost layer: everything below it (extractors and the handler) counts as handler time
+pub async fn handler<B>(req: Request<B>, next: Next<B>) -> Response {
+	let started = Instant::now();
+	let res = next.run(req).await;
+	let _ = TIMINGS.try_with(|timings| add(&timings.handler, started.elapsed()));
+
+	res
+}
+
+// outermost layer: logs requests slower than the configured threshold
+pub async fn observe<B>(
+	extract::State(state): extract::State<State>,
+	req: Request<B>,
+	next: Next<B>,
+) -> Response {
+	let Some(threshold) = state.slow_request else {
+		return next.run(req).await;
+	};
+	let method = req.method().clone();
+	let uri = req.uri().clone();
+	let timings = Arc::new(Timings::default());
+	let started = Instant::now();
+	let res = TIMINGS.scope(timings.clone(), next.run(req)).await;
+	let total = started.elapsed();
+
+	if total >= threshold {
+		let total = total.as_micros() as u64;
+		let handler = timings.handler.load(Ordering::Relaxed);
+		let storage = timings.storage.load(Ordering::Relaxed);
+
+		tracing::warn!(
+			%method,
+			path = uri.path(),
+			status = res.status().as_u16(),
+			total_ms = millis(total),
+			middleware_ms = millis(total.saturating_sub(handler)),
+			handler_ms = millis(handler.saturating_sub(storage)),
+			storage_ms = millis(storage),
+			"slow request"
+		);
+	}
+
+	res
+}
@@ -0,0 +1,92 @@
+use serde::{self, Serialize};
+use utoipa::ToSchema;
+
+use crate::{dto, Error};
+
+pub const MAX_ID_LEN: usize = 128;
+pub const MAX_TOKEN_LEN: usize = 256;
+// ten years; expiry times stay well within what the stores can hold
+pub const MAX_TTL: u64 = 10 * 365 * 24 * 60 * 60;
+
+#[derive(Serialize, Clone, PartialEq, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct FieldError {
+	pub field: String,
+	pub message: String,
+}
+
+// collects every problem with a request so that clients can fix them in one go
+#[derive(Default, Debug)]
+pub struct Errors(Vec<FieldError>);
+
+impl Errors {
+	pub fn check(&mut self, ok: bool, field: impl Into<String>, message: impl Into<String>) {
+		if !ok {
+			self.0.push(FieldError {
+				field: field.into(),
+				message: message.into(),
+			});
+		}
+	}
+
+	pub fn into_result(self) -> Result<(), Error> {
+		if self.0.is_empty() {
+			Ok(())
+		} else {
+			Err(Error::Validation(self.0))
+		}
+	}
+}
+
+pub trait Validate {
+	fn validate(&self, errors: &mut Errors);
+}
+
+pub fn id(errors: &mut Errors, field: &'static str, id: &str) {
+	errors.check(!id.is_empty(), field, "must not be empty");
+	errors.check(
+		id.len() <= MAX_ID_LEN,
+		field,
+		format!("must be at most {} characters", MAX_ID_LEN),
+	);
+	errors.check(
+		id.chars()
+			.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')),
+		field,
+		"may only contain letters, digits, '-', '_', '.' and ':'",
+	);
+}
+
+pub fn token(errors: &mut Errors, field: &'static str, token: &str) {
+	errors.check(!token.is_empty(), field, "must not be empty");
+	errors.check(
+		token.len() <= MAX_TOKEN_LEN,
+		field,
+		format!("must be at most {} characters", MAX_TOKEN_LEN),
+	);
+	errors.check(
+		token.chars().all(|c| c.is_ascii_graphic()),
+		field,
+		"may only contain printable ascii without spaces",
+	);
+}
+
+impl Validate for dto::v1::LockRequest {
+	fn validate(&self, errors: &mut Errors) {
+		token(errors, "token", &self.token);
+		errors.check(self.ttl != Some(0), "ttl", "must be at least 1 second");
+		errors.check(
+			self.ttl.is_none_or(|ttl| ttl <= MAX_TTL),
+			"ttl",
+			format!("must be at most {} seconds", MAX_TTL),
+		);
+		errors.check(self.max_uses != Some(0), "max_uses", "must be at least 1");
+	}
+}
+
+impl Validate for dto::v1::VerifyRequest {
+	fn validate(&self, errors: &mut Errors) {
+		id(errors, "id", &self.id);
+		token(errors, "token", &self.token);
+	}
+}
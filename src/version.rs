@@ -0,0 +1,54 @@
+use axum::extract;
+use serde::{self, Serialize};
+use utoipa::ToSchema;
+
+use crate::{json::Reply, State};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_COMMIT: &str = env!("TOUCHID_GIT_COMMIT");
+pub const BUILD_TIMESTAMP: &str = env!("TOUCHID_BUILD_TIMESTAMP");
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "self::serde")]
+pub struct Info {
+	pub version: &'static str,
+	pub git_commit: &'static str,
+	pub build_timestamp: &'static str,
+	pub features: Vec<&'static str>,
+	pub storage: &'static str,
+}
+
+pub fn features() -> Vec<&'static str> {
+	[
+		("console", cfg!(feature = "console")),
+		("jemalloc", cfg!(feature = "jemalloc")),
+		("otlp", cfg!(feature = "otlp")),
+		("postgres", cfg!(feature = "postgres")),
+		("pprof", cfg!(feature = "pprof")),
+		("redis", cfg!(feature = "redis")),
+		("sqlite", cfg!(feature = "sqlite")),
+	]
+	.into_iter()
+	.filter_map(|(name, enabled)| enabled.then_some(name))
+	.collect()
+}
+
+pub fn info(state: &State) -> Info {
+	Info {
+		version: VERSION,
+		git_commit: GIT_COMMIT,
+		build_timestamp: BUILD_TIMESTAMP,
+		features: features(),
+		storage: state.storage(),
+	}
+}
+
+#[utoipa::path(
+	get,
+	path = "/version",
+	tag = "service",
+	responses((status = 200, description = "build information", body = Info)),
+)]
+pub async fn version(extract::State(state): extract::State<State>) -> Reply<Info> {
+	state.json.reply(info(&state))
+}